@@ -0,0 +1,444 @@
+//! Level editor for potions game
+//!
+//! Edits a single level and saves it as RON.
+use std::{
+    env, fs,
+    io::{self, stdout, Stdout},
+    path::PathBuf,
+    time::Duration,
+};
+
+use bevy_math::Vec2;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use ratatui::{layout::Flex, prelude::*, widgets::*};
+
+use potions::units::{self, ml, mm};
+use potions::{solver, tui, Goal, Layer, Level, Liquid, Material, Object, ObjectKind, ObjectShape, Shape, Vial};
+
+/// Volume added by each push of a liquid (units: ml).
+const POUR_STEP: f32 = 10.0;
+/// How far from a goal's volume a vial may be and still meet it (units: ml).
+const GOAL_TOLERANCE: f32 = 0.5;
+
+/// Liquids to push, cycled with 'w'.
+const LIQUIDS: [(&str, Liquid); 3] = [
+    ("water", Liquid::WATER),
+    ("oil", Liquid::OIL),
+    ("honey", Liquid::HONEY),
+];
+
+const MATERIALS: [(&str, Material); 3] = [
+    ("glass", Material::GLASS),
+    ("wood", Material::WOOD),
+    ("metal", Material::METAL),
+];
+
+const OBJECT_SHAPES: [(&str, ObjectShape); 3] = [
+    ("circle", ObjectShape::Circle),
+    ("capsule", ObjectShape::Capsule { half_length: 2.0 }),
+    ("box", ObjectShape::Box { half: Vec2::new(1.5, 1.0) }),
+];
+
+fn material_name(material: &Material) -> &'static str {
+    MATERIALS.iter().find(|(_, m)| m == material).map_or("custom", |(name, _)| name)
+}
+
+fn usage() -> io::Result<()> {
+    eprintln!("Usage: editor <level.ron>");
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args();
+    let _ = args.next();
+    let Some(path) = args.next() else {
+        return usage();
+    };
+    let path = PathBuf::from(path);
+    let level = if path.exists() {
+        Level::load(&path)?
+    } else {
+        Level {
+            potions: vec![Vial::default()],
+            ..Default::default()
+        }
+    };
+    let mut editor = Editor::new(path, level);
+    editor.message = editor.warnings();
+    let mut terminal = init_terminal()?;
+    while !editor.quit {
+        terminal.draw(|frame| editor.ui(frame))?;
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    editor.handle_key(key.code);
+                }
+            }
+        }
+    }
+    restore_terminal()
+}
+
+enum Mode {
+    Vials,
+    /// Placing objects with a cursor (units: mm).
+    /// `shape` indexes [OBJECT_SHAPES].
+    Place { pos: Vec2, kind: ObjectKind, size: f32, shape: usize },
+}
+
+struct Editor {
+    path: PathBuf,
+    level: Level,
+    cursor: usize,
+    color: usize,
+    /// Index into [LIQUIDS].
+    liquid: usize,
+    mode: Mode,
+    message: String,
+    quit: bool,
+}
+
+impl Editor {
+    fn new(path: PathBuf, level: Level) -> Self {
+        Self {
+            path,
+            level,
+            cursor: 0,
+            color: 0,
+            liquid: 0,
+            mode: Mode::Vials,
+            message: String::new(),
+            quit: false,
+        }
+    }
+
+    fn vial(&mut self) -> Option<&mut Vial> {
+        self.level.potions.get_mut(self.cursor)
+    }
+
+    fn next_object_id(&self) -> u64 {
+        self.level
+            .potions
+            .iter()
+            .flat_map(|v| &v.objects)
+            .map(|o| o.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        self.message.clear();
+        match self.mode {
+            Mode::Vials => self.handle_vials_key(code),
+            Mode::Place { .. } => self.handle_place_key(code),
+        }
+    }
+
+    fn handle_vials_key(&mut self, code: KeyCode) {
+        let count = self.level.potions.len();
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Right | KeyCode::Char('l') if count > 0 => {
+                self.cursor = (self.cursor + 1).rem_euclid(count)
+            }
+            KeyCode::Left | KeyCode::Char('h') if count > 0 => {
+                self.cursor = (self.cursor + count - 1).rem_euclid(count)
+            }
+            KeyCode::Char('a') => {
+                let vial = self.vial().map(|v| Vial {
+                    layers: vec![],
+                    objects: vec![],
+                    ..v.clone()
+                });
+                self.cursor = (self.cursor + 1).min(count);
+                self.level.potions.insert(self.cursor, vial.unwrap_or_default());
+            }
+            KeyCode::Char('x') if count > 0 => {
+                self.level.potions.remove(self.cursor);
+                self.cursor = self.cursor.min(count.saturating_sub(2));
+            }
+            KeyCode::Char('+') => self.edit_vial(|v| v.max_volume = v.max_volume + ml(POUR_STEP)),
+            KeyCode::Char('-') => self.edit_vial(|v| {
+                v.max_volume = units::max(units::max(v.max_volume - ml(POUR_STEP), v.vol()), ml(POUR_STEP))
+            }),
+            KeyCode::Char(']') => self.edit_vial(|v| v.size.x += 1.0),
+            KeyCode::Char('[') => self.edit_vial(|v| v.size.x = (v.size.x - 1.0).max(5.0)),
+            KeyCode::Char('}') => self.edit_vial(|v| v.size.y += 5.0),
+            KeyCode::Char('{') => self.edit_vial(|v| v.size.y = (v.size.y - 5.0).max(10.0)),
+            KeyCode::Char('f') => self.edit_vial(|v| {
+                v.shape = match v.shape {
+                    Shape::Straight => Shape::RoundBottom,
+                    Shape::RoundBottom => Shape::Tapered { bottom: 0.5 },
+                    Shape::Tapered { .. } => Shape::Flask { neck: 0.4 },
+                    _ => Shape::Straight,
+                }
+            }),
+            KeyCode::Char('m') => {
+                self.edit_vial(|v| v.physical = !v.physical);
+                self.message = self.warnings();
+            }
+            KeyCode::Char('M') => self.edit_vial(|v| {
+                let i = MATERIALS.iter().position(|(_, m)| *m == v.material).map_or(0, |i| i + 1);
+                v.material = MATERIALS[i % MATERIALS.len()].1;
+            }),
+            // There is nothing to cycle through without a palette.
+            KeyCode::Char('c' | 'C') if self.level.palette.is_empty() => (),
+            KeyCode::Char('c') => self.color = (self.color + 1).rem_euclid(self.level.palette.len()),
+            KeyCode::Char('C') => {
+                let n = self.level.palette.len();
+                self.color = (self.color + n - 1).rem_euclid(n)
+            }
+            KeyCode::Char('w') => self.liquid = (self.liquid + 1).rem_euclid(LIQUIDS.len()),
+            KeyCode::Char('p') => {
+                let id = self.color;
+                let liquid = LIQUIDS[self.liquid].1;
+                self.edit_vial(|v| {
+                    let volume = units::min(ml(POUR_STEP), v.max_volume - v.vol());
+                    if volume <= ml(0.0) {
+                        return;
+                    }
+                    match v.layers.last_mut() {
+                        Some(Layer::Liquid { id: top, volume: v, liquid: l, .. }) if *top == id && *l == liquid => {
+                            *v = *v + volume
+                        }
+                        _ => v.layers.push(Layer::new(id, volume, liquid)),
+                    }
+                });
+            }
+            KeyCode::Char('P') => self.edit_vial(|v| {
+                v.layers.pop();
+            }),
+            KeyCode::Char('i') => self.edit_vial(|v| {
+                // The editor has no clock, so what boils does so at once.
+                v.heat(solver::HEAT_STEP);
+                v.boil(f32::INFINITY);
+            }),
+            KeyCode::Char('I') => self.edit_vial(|v| {
+                v.heat(-solver::HEAT_STEP);
+            }),
+            KeyCode::Char('g') => {
+                let vial = self.cursor;
+                let (volume, tolerance) = (ml(POUR_STEP), ml(GOAL_TOLERANCE));
+                self.level.goal = match self.level.goal {
+                    Goal::Unmix => Goal::BreakSeed,
+                    Goal::BreakSeed => Goal::Amount { vial, id: self.color, volume, tolerance },
+                    Goal::Amount { .. } => Goal::Measure { vial, volume, tolerance },
+                    Goal::Measure { .. } => Goal::Boil { vial },
+                    Goal::Boil { .. } => Goal::Freeze { vial },
+                    _ => Goal::Unmix,
+                }
+            }
+            KeyCode::Char('o') => {
+                if let Some(vial) = self.vial() {
+                    let pos = vial.size / 2.0;
+                    self.mode = Mode::Place {
+                        pos,
+                        kind: ObjectKind::Seed,
+                        size: 2.0,
+                        shape: 0,
+                    };
+                }
+            }
+            KeyCode::Char('s') => self.save(),
+            _ => {}
+        }
+    }
+
+    fn handle_place_key(&mut self, code: KeyCode) {
+        let next_id = self.next_object_id();
+        let Some(vial_size) = self.vial().map(|v| v.size) else {
+            self.mode = Mode::Vials;
+            return;
+        };
+        let Mode::Place {
+            ref mut pos,
+            ref mut kind,
+            ref mut size,
+            ref mut shape,
+        } = self.mode
+        else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('o') => self.mode = Mode::Vials,
+            KeyCode::Left | KeyCode::Char('h') => pos.x -= 1.0,
+            KeyCode::Right | KeyCode::Char('l') => pos.x += 1.0,
+            KeyCode::Down | KeyCode::Char('j') => pos.y -= 1.0,
+            KeyCode::Up | KeyCode::Char('k') => pos.y += 1.0,
+            KeyCode::Char('+') => *size += 0.5,
+            KeyCode::Char('-') => *size = (*size - 0.5).max(0.5),
+            KeyCode::Char('t') => {
+                *kind = match kind {
+                    ObjectKind::Seed => ObjectKind::Creature,
+                    ObjectKind::Creature => ObjectKind::Plant,
+                    ObjectKind::Plant => ObjectKind::Shell,
+                    ObjectKind::Shell => ObjectKind::Kernel,
+                    ObjectKind::Kernel => ObjectKind::Seed,
+                }
+            }
+            KeyCode::Char('r') => *shape = (*shape + 1).rem_euclid(OBJECT_SHAPES.len()),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let object = Object {
+                    kind: *kind,
+                    pos: *pos,
+                    size: mm(*size),
+                    shape: OBJECT_SHAPES[*shape].1.clone(),
+                    id: next_id,
+                    ..Default::default()
+                };
+                self.level.potions[self.cursor].objects.push(object);
+            }
+            KeyCode::Char('d') => {
+                let p = *pos;
+                self.level.potions[self.cursor]
+                    .objects
+                    .retain(|o| !o.contains(p) && o.pos.distance(p) > 1.0);
+            }
+            _ => {}
+        }
+        if let Mode::Place { ref mut pos, .. } = self.mode {
+            *pos = pos.clamp(Vec2::ZERO, vial_size);
+        }
+    }
+
+    fn edit_vial(&mut self, f: impl FnOnce(&mut Vial)) {
+        if let Some(vial) = self.vial() {
+            f(vial);
+        }
+    }
+
+    fn warnings(&self) -> String {
+        let warnings: Vec<String> = self.level.warnings().iter().map(|w| format!("{}.", w)).collect();
+        warnings.join(" ")
+    }
+
+    fn save(&mut self) {
+        if let Err(e) = self.level.validate() {
+            self.message = format!("Not saved: {}.", e);
+            return;
+        }
+        let moves = match solver::solve(&Level { par: None, ..self.level.clone() }) {
+            Ok(moves) => moves,
+            Err(e) => {
+                self.message = format!("Not saved: {}.", e);
+                return;
+            }
+        };
+        self.level.par = Some(moves.len());
+        let contents = ron::ser::to_string_pretty(&self.level, ron::ser::PrettyConfig::default())
+            .expect("serialize");
+        self.message = match fs::write(&self.path, contents) {
+            Ok(()) => format!("Saved {} (par {}).", self.path.display(), moves.len()),
+            Err(e) => format!("Not saved: {}.", e),
+        };
+    }
+
+    fn ui(&self, frame: &mut Frame) {
+        let [title, content, info, help, message] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Percentage(100),
+            Constraint::Length(4),
+            Constraint::Length(2),
+            Constraint::Length(1),
+        ])
+        .areas(frame.size());
+        frame.render_widget(
+            Paragraph::new(format!("{}  Goal: {}", self.path.display(), self.level.goal))
+                .alignment(Alignment::Center),
+            title,
+        );
+
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(std::iter::repeat_n(Constraint::Fill(1), self.level.potions.len()))
+            .margin(2)
+            .flex(Flex::Center)
+            .spacing(4);
+        let palette = &self.level.palette;
+        for (i, rect) in layout.split(content).iter().enumerate() {
+            let [potion, footer] =
+                Layout::vertical([Constraint::Percentage(100), Constraint::Length(1)]).areas(*rect);
+            let widget = tui::VialWidget::new(&self.level.potions[i], palette);
+            frame.render_widget(widget.clone(), potion);
+            if i == self.cursor {
+                if let Mode::Place { pos, .. } = self.mode {
+                    if let Some((x, y)) = widget.to_cell(potion, pos) {
+                        frame.render_widget(Span::raw("+").reversed(), Rect::new(x, y, 1, 1));
+                    }
+                }
+            }
+            frame.render_widget(
+                Paragraph::new(if self.cursor == i { "/\\" } else { "" })
+                    .alignment(Alignment::Center),
+                footer,
+            );
+        }
+
+        let mut lines = vec![];
+        if let Some(vial) = self.level.potions.get(self.cursor) {
+            lines.push(Line::from(format!(
+                "Vial {}: max volume {}, size {} x {} mm, {:?}, {}, {} objects{}",
+                self.cursor + 1,
+                vial.max_volume,
+                vial.size.x,
+                vial.size.y,
+                vial.shape,
+                material_name(&vial.material),
+                vial.objects.len(),
+                if vial.physical { format!(", physical (holds {:.1})", vial.capacity()) } else { String::new() }
+            )));
+            let layers: Vec<String> = vial
+                .layers
+                .iter()
+                .map(|l| {
+                    let state = if l.is_solid() { " frozen" } else if l.is_boiling() { " boiling" } else { "" };
+                    format!("{}:{} {:.0}°C{}", l.id(), l.volume(), l.temperature(), state)
+                })
+                .collect();
+            lines.push(Line::from(format!("Layers: {}", layers.join(" "))));
+        }
+        let mut swatches = vec![Span::raw("Color: ")];
+        for (i, color) in palette.iter().enumerate() {
+            let style = Style::new().bg(color.clone().into());
+            swatches.push(Span::styled(if i == self.color { "[]" } else { "  " }, style));
+            swatches.push(Span::raw(" "));
+        }
+        swatches.push(Span::raw(format!(" Liquid: {}", LIQUIDS[self.liquid].0)));
+        lines.push(Line::from(swatches));
+        if let Mode::Place { pos, kind, size, shape } = self.mode {
+            lines.push(Line::from(format!(
+                "Placing {:?} {} size {} at ({}, {})",
+                kind, OBJECT_SHAPES[shape].0, size, pos.x, pos.y
+            )));
+        }
+        frame.render_widget(Paragraph::new(lines), info);
+
+        let keys = match self.mode {
+            Mode::Vials => {
+                "h/l: vial  a/x: add/remove  +/-: max volume  [/]: width  {/}: height  f: shape\n\
+                 m: physical  M: material  c/C: color  w: liquid  p/P: push/pop liquid  i/I: heat/cool  o: objects  g: goal  s: save  q: quit"
+            }
+            Mode::Place { .. } => {
+                "hjkl: move  +/-: size  t: kind  r: shape  enter: place  d: delete  esc: done"
+            }
+        };
+        frame.render_widget(Paragraph::new(keys).dark_gray(), help);
+        frame.render_widget(Paragraph::new(self.message.as_str()).yellow(), message);
+    }
+}
+
+fn init_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(stdout()))
+}
+
+fn restore_terminal() -> io::Result<()> {
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    Ok(())
+}
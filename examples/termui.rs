@@ -6,32 +6,47 @@ use bevy_app::AppExit;
 use bevy_state::prelude::*;
 use bevy_log::prelude::*;
 use bevy_defer::AsyncCommandsExtension;
+use bevy_math::Vec2;
 // use bevy_state::prelude::*;
 //
 use std::{
     env,
     fs::{self, File},
-    io::{self, stdout, Read, Stdout, Write},
+    io::{self, stdout, Stdout, Write},
     time::Duration,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    thread,
 };
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use derived_deref::{Deref, DerefMut};
 use ratatui::{layout::Flex, prelude::*, widgets::*};
 
+use potions::fluid::{FluidBackend, Layered, Particles};
 use potions::vial_physics::VialPhysics;
+use potions::score::{Moves, Profile, Score};
 use potions::*;
+use potions::units::{ml, LengthExt};
 use async_channel::{Sender, Receiver};
 use crate::script::{self, Input, Output};
 
+const PROFILE_PATH: &str = "potions-profile.ron";
+/// Progress of a transfer per tick.
+const POUR_STEP: f32 = 0.02;
+/// How hard a finished pour pushes the surfaces of its vials (units: mm/s).
+const POUR_SPLASH: f32 = 60.0;
+/// How far from an object a click still inspects it (units: mm).
+const INSPECT_RADIUS: f32 = 2.0;
 
 fn usage() -> io::Result<()> {
-    eprintln!("Usage: termui");
+    eprintln!("Usage: termui [--particles]");
     eprintln!("       termui write <dir>");
     eprintln!("       termui read <dir>");
     Ok(())
@@ -49,6 +64,7 @@ fn main() -> io::Result<()> {
         Some("read") => {
             my_app.levels = read_levels(&args.next().expect("dir"))?;
         }
+        Some("--particles") => my_app.particles = Some(vec![]),
         Some(command) => {
             eprintln!("error: invalid command {:?}.", command);
             return usage();
@@ -69,7 +85,7 @@ fn main() -> io::Result<()> {
     .add_systems(OnEnter(AppState::GotoLevel), goto_level)
     // .insert_state(Popup::Message("hi".into()))
     .insert_state(Popup::None)
-    .insert_state(AppState::GotoLevel);
+    .insert_state(AppState::LevelSelect);
     let mut cont = true;
     while cont {
         app.update();
@@ -106,10 +122,7 @@ fn read_levels(dir: &str) -> io::Result<Vec<Level>> {
         if path.is_file() {
             if let Some(extension) = path.extension() {
                 if extension == "ron" {
-                    let mut file = File::open(&path)?;
-                    let mut contents = String::new();
-                    file.read_to_string(&mut contents)?;
-                    levels.push(ron::from_str(&contents).expect("level"));
+                    levels.push(Level::load(&path)?);
                 }
             }
         }
@@ -131,7 +144,30 @@ struct App {
     level_index: usize,
     // state: AppState,
     channels: Option<ScriptChannels>,
-    transfer: Option<(Transfer, f32)>
+    /// The transfer, its progress and the two vials before it started.
+    transfer: Option<(Transfer, f32, (Vial, Vial))>,
+    moves: Moves,
+    par: Option<usize>,
+    /// Par of each level solved so far, by index, so the solver runs once
+    /// per level rather than on every restart.
+    pars: HashMap<usize, Option<usize>>,
+    /// Levels being solved in the background. Their pars arrive on
+    /// `par_output`.
+    solving: HashSet<usize>,
+    par_input: Sender<(usize, Option<usize>)>,
+    par_output: Receiver<(usize, Option<usize>)>,
+    completed: bool,
+    profile: Profile,
+    level_select: tui::LevelSelectState,
+    /// Where each vial was last drawn, for hit-testing the mouse.
+    vial_areas: Vec<Rect>,
+    /// The vial a mouse drag started from.
+    drag: Option<usize>,
+    /// Volume typed before a pour. Without one the whole layer is poured.
+    amount: Option<u32>,
+    /// The liquid in each vial as particles, if the game was started with
+    /// `--particles`. Otherwise the liquid is layered.
+    particles: Option<Vec<Particles>>,
 }
 
 #[derive(Resource)]
@@ -149,11 +185,14 @@ struct Term(Terminal<CrosstermBackend<Stdout>>);
 #[derive(States, Debug, Hash, PartialEq, Eq, Clone)]
 enum Popup {
     Message(String),
+    /// Details of an object; dismissed by any key or click.
+    Inspect(String),
     None
 }
 
 #[derive(States, Debug, Hash, PartialEq, Eq, Clone)]
 enum AppState {
+    LevelSelect,
     Game,
     GotoLevel,
     Transfer,
@@ -186,10 +225,7 @@ fn goto_level(mut app: ResMut<App>,
 mut commands: Commands) {
     let index: usize = app.level_index;
     warn!("goto level {index}");
-    if index < app.levels.len() {
-        app.potions = app.levels[index].potions.to_vec();
-        app.vial_physics = app.potions.iter().map(VialPhysics::new).collect();
-        app.level_index = index;
+    if app.goto_level(index) {
         pal.0 = app.levels[index].palette.clone();
         // Setup script.
         let (in_s, in_r) = async_channel::unbounded();
@@ -212,85 +248,195 @@ mut commands: Commands) {
 
 fn read_script_output(mut app: ResMut<App>,
                       mut popup: ResMut<NextState<Popup>>,
-                      mut app_exit: ResMut<Events<bevy_app::AppExit>>,
+                      mut next_state: ResMut<NextState<AppState>>,
 ) {
     if let Some(ScriptChannels { ref mut output, .. }) = app.channels {
         if let Ok(x) = output.try_recv() {
             match x {
                 Output::Message(s) => popup.set(Popup::Message(s)),
                 Output::End => {
-                    app_exit.send_default();
+                    popup.set(Popup::None);
+                    app.leave_level();
+                    let count = app.levels.len();
+                    app.level_select.next(count);
+                    next_state.set(AppState::LevelSelect);
                 }
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn app_update(mut app: ResMut<App>,
               mut terminal: ResMut<Term>,
               state: Res<State<AppState>>,
               popup: Res<State<Popup>>,
               mut next_state: ResMut<NextState<AppState>>,
+              mut next_popup: ResMut<NextState<Popup>>,
               mut app_exit: ResMut<Events<bevy_app::AppExit>>,
               mut pal: ResMut<Pal>) {
-    let _ = terminal.draw(|frame| app.ui(frame, state.get(), popup.get()));
+    let _ = terminal.draw(|frame| app.ui(frame, state.get(), popup.get(), &pal));
 
     let timeout = Duration::from_millis(16);
     let level_index = app.level_index;
     if event::poll(timeout).expect("poll") {
-        if let Ok(Event::Key(key)) = event::read() {
-            let quit = match key.code {
-                KeyCode::Char('q') => true,
-                KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => true,
-                _ => false
-            };
-            if quit {
-                app_exit.send_default();
+        match event::read() {
+            Ok(Event::Key(_)) | Ok(Event::Mouse(MouseEvent { kind: MouseEventKind::Down(_), .. }))
+                if matches!(popup.get(), Popup::Inspect(_)) =>
+            {
+                next_popup.set(Popup::None);
             }
-            match state.get() {
-                AppState::Game => match key.code {
-                    KeyCode::Char('m') => {
-                        let c: usize = app.cursor;
-                        // let palette = &mut app.levels[level_index].palette;
-                        app.potions[c].mix(&mut pal);
-                    }
-                    KeyCode::Char('r') => {
-                        app.goto_level(level_index);
-                    }
-                    KeyCode::Char('n') => {
-                        app.goto_level(level_index + 1);
+            Ok(Event::Key(key)) => {
+                let quit = match key.code {
+                    KeyCode::Char('q') => true,
+                    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => true,
+                    _ => false
+                };
+                if quit {
+                    app_exit.send_default();
+                }
+                match state.get() {
+                    AppState::LevelSelect => match key.code {
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            let count = app.levels.len();
+                            app.level_select.next(count);
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => app.level_select.previous(),
+                        KeyCode::Enter | KeyCode::Char(' ') => {
+                            let index = app.level_select.selected;
+                            if app.profile.is_unlocked(index) {
+                                app.level_index = index;
+                                next_state.set(AppState::GotoLevel);
+                            }
+                        }
+                        _ => {}
+                    },
+                    AppState::Game => match key.code {
+                        KeyCode::Char(c @ '0'..='9') if app.levels[app.level_index].goal.allows_measuring() => {
+                            let digit = c.to_digit(10).unwrap_or(0);
+                            app.amount = Some(app.amount.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                        }
+                        KeyCode::Backspace => app.amount = app.amount.map(|a| a / 10).filter(|a| *a > 0),
+                        KeyCode::Esc if app.amount.is_some() => app.amount = None,
+                        KeyCode::Esc => {
+                            app.leave_level();
+                            next_state.set(AppState::LevelSelect);
+                        }
+                        KeyCode::Char('m') => {
+                            let c: usize = app.cursor;
+                            // let palette = &mut app.levels[level_index].palette;
+                            if app.mix(c, &mut pal) {
+                                app.moves.mixes += 1;
+                            }
+                        }
+                        // Heat acts on layers, which particles don't follow.
+                        KeyCode::Char('i' | 'I') if app.particles.is_some() => {}
+                        KeyCode::Char('i') => {
+                            let c = app.cursor;
+                            if app.potions[c].heat(solver::HEAT_STEP) {
+                                app.moves.heats += 1;
+                            }
+                        }
+                        KeyCode::Char('I') => {
+                            let c = app.cursor;
+                            if app.potions[c].heat(-solver::HEAT_STEP) {
+                                app.moves.heats += 1;
+                            }
+                        }
+                        KeyCode::Char('r') => {
+                            app.restart();
+                        }
+                        KeyCode::Char(c @ ('n' | 'p')) => {
+                            let index = if c == 'n' { level_index + 1 } else { level_index.saturating_sub(1) };
+                            // Go the way the level select does, so locked levels stay locked.
+                            if index != level_index && index < app.levels.len() && app.profile.is_unlocked(index) {
+                                app.leave_level();
+                                app.level_index = index;
+                                next_state.set(AppState::GotoLevel);
+                            }
+                        }
+                        KeyCode::Char(' ') | KeyCode::Up => match app.selected {
+                            Some(i) => {
+                                let cursor = app.cursor;
+                                if i == cursor {
+                                    app.selected = None;
+                                } else if app.pour(i, cursor) {
+                                    next_state.set(AppState::Transfer);
+                                } else {
+                                    app.selected = None;
+                                }
+                            }
+                            None => app.selected = Some(app.cursor),
+                        },
+                        KeyCode::Right | KeyCode::Char('l') => {
+                            app.cursor = (app.cursor + 1).rem_euclid(app.potions.len())
+                        }
+                        KeyCode::Left | KeyCode::Char('h') => {
+                            app.cursor = (app.cursor + app.potions.len() - 1)
+                                .rem_euclid(app.potions.len())
+                        }
+                        _ => {}
+                    },
+                    AppState::End => {}
+                    _ => {}
+                }
+            }
+            Ok(Event::Mouse(mouse)) => match state.get() {
+                AppState::LevelSelect => match mouse.kind {
+                    MouseEventKind::ScrollDown => {
+                        let count = app.levels.len();
+                        app.level_select.next(count);
                     }
-                    KeyCode::Char('p') => {
-                        app.goto_level(level_index.saturating_sub(1));
+                    MouseEventKind::ScrollUp => app.level_select.previous(),
+                    _ => {}
+                },
+                AppState::Game => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let Some((i, pos)) = app.hit(mouse.column, mouse.row) else {
+                            app.selected = None;
+                            return;
+                        };
+                        // With a vial selected a click pours rather than inspects.
+                        if app.selected.is_none() {
+                            if let Some(details) = app.inspect(i, pos) {
+                                next_popup.set(Popup::Inspect(details));
+                                return;
+                            }
+                        }
+                        app.cursor = i;
+                        match app.selected {
+                            None => {
+                                app.selected = Some(i);
+                                app.drag = Some(i);
+                            }
+                            Some(s) if s == i => app.selected = None,
+                            Some(s) => {
+                                if app.pour(s, i) {
+                                    next_state.set(AppState::Transfer);
+                                } else {
+                                    app.selected = None;
+                                }
+                            }
+                        }
                     }
-                    KeyCode::Char(' ') | KeyCode::Up => match app.selected {
-                        Some(i) => {
-                            if i == app.cursor {
-                                app.selected = None;
-                            } else if let Some(transfer) =
-                                app.potions[i].pour(&app.potions[app.cursor])
-                            {
-                                // app.state = AppState::Transfer(transfer, 0.0);
-                                app.transfer = Some((transfer, 0.0));
+                    MouseEventKind::Up(MouseButton::Left) => {
+                        let Some(from) = app.drag.take() else {
+                            return;
+                        };
+                        if let Some((i, _)) = app.hit(mouse.column, mouse.row).filter(|(i, _)| *i != from) {
+                            app.cursor = i;
+                            if app.pour(from, i) {
                                 next_state.set(AppState::Transfer);
                             } else {
                                 app.selected = None;
                             }
                         }
-                        None => app.selected = Some(app.cursor),
-                    },
-                    KeyCode::Right | KeyCode::Char('l') => {
-                        app.cursor = (app.cursor + 1).rem_euclid(app.potions.len())
-                    }
-                    KeyCode::Left | KeyCode::Char('h') => {
-                        app.cursor = (app.cursor + app.potions.len() - 1)
-                            .rem_euclid(app.potions.len())
                     }
                     _ => {}
                 },
-                AppState::End => {}
                 _ => {}
-            }
+            },
+            _ => {}
         }
     }
 }
@@ -303,6 +449,16 @@ fn tick(mut app: ResMut<App>, state: Res<State<AppState>>, mut next_state: ResMu
 impl App {
     fn new() -> App {
         let levels = levels();
+        let profile = Profile::load(PROFILE_PATH).unwrap_or_else(|e| {
+            warn!("could not load profile: {e}");
+            Profile::default()
+        });
+        // Start on the last unlocked level.
+        let selected = (0..levels.len())
+            .rev()
+            .find(|i| profile.is_unlocked(*i))
+            .unwrap_or(0);
+        let (par_input, par_output) = async_channel::unbounded();
         App {
             tick_count: 0,
             cursor: 0,
@@ -314,6 +470,22 @@ impl App {
             // state: AppState::Game,
             channels: None,
             transfer: None,
+            moves: Moves::default(),
+            par: None,
+            pars: HashMap::new(),
+            solving: HashSet::new(),
+            par_input,
+            par_output,
+            completed: false,
+            profile,
+            level_select: tui::LevelSelectState {
+                selected,
+                ..Default::default()
+            },
+            vial_areas: vec![],
+            drag: None,
+            amount: None,
+            particles: None,
         }
     }
 
@@ -322,39 +494,186 @@ impl App {
             false
         } else {
             self.potions = self.levels[index].potions.to_vec();
-            self.vial_physics = self.potions.iter().map(VialPhysics::new).collect();
+            self.vial_physics = self.potions.iter().map(|v| VialPhysics::new(v).with_waves()).collect();
+            if let Some(particles) = &mut self.particles {
+                *particles = self.vial_physics.iter_mut().zip(&self.potions).map(|(p, v)| Particles::new(p, v)).collect();
+            }
             self.level_index = index;
+            self.moves = Moves::default();
+            self.par = self.request_par(index);
+            self.completed = false;
+            self.amount = None;
             true
         }
     }
 
+    /// The level's par if it is known yet. Otherwise start solving it on
+    /// another thread so the game doesn't stall; [App::on_tick] picks up
+    /// the result.
+    fn request_par(&mut self, index: usize) -> Option<usize> {
+        let level = &self.levels[index];
+        if let Some(par) = level.par.or_else(|| self.pars.get(&index).copied().flatten()) {
+            return Some(par);
+        }
+        if !self.pars.contains_key(&index) && self.solving.insert(index) {
+            let level = level.clone();
+            let input = self.par_input.clone();
+            thread::spawn(move || {
+                let _ = input.try_send((index, solver::par(&level)));
+            });
+        }
+        None
+    }
 
-    fn sync_objects(&mut self, vial_index: usize) {
-        for obj in &self.potions[vial_index].objects {
-            self.vial_physics[vial_index].insert(obj);
+    /// Stop the level's script.
+    fn leave_level(&mut self) {
+        if let Some(ScriptChannels { ref mut input, ..}) = self.channels.take() {
+            let _ = input.try_send(Input::Abort);
+        }
+        self.selected = None;
+        self.level_select.selected = self.level_index;
+    }
+
+    /// Reset the vials but keep counting moves.
+    pub fn restart(&mut self) {
+        let moves = self.moves;
+        if self.goto_level(self.level_index) {
+            self.moves = Moves {
+                restarts: moves.restarts + 1,
+                ..moves
+            };
+        }
+    }
+
+    fn complete_level(&mut self) {
+        self.completed = true;
+        let score = Score::new(self.moves, self.par);
+        if self.profile.record(self.level_index, score) {
+            if let Err(e) = self.profile.save(PROFILE_PATH) {
+                warn!("could not save profile: {e}");
+            }
+        }
+        if let Some(ScriptChannels { ref mut input, ..}) = self.channels {
+            input.try_send(Input::GoalReached(score)).expect("script send");
         }
     }
 
+
+    fn sync_objects(&mut self, vial_index: usize) {
+        self.vial_physics[vial_index].sync(&self.potions[vial_index]);
+    }
+
     fn step(&mut self) {
+        let rules = &self.levels[self.level_index].rules;
+        let mut layered = Layered;
         for (i, potion) in self.potions.iter_mut().enumerate() {
             let phys = &mut self.vial_physics[i];
+            let fluid: &mut dyn FluidBackend = match &mut self.particles {
+                Some(particles) => &mut particles[i],
+                None => &mut layered,
+            };
             phys.kick_on_enter(potion);
-            phys.add_buoyancy_forces(potion);
+            fluid.add_forces(phys, potion);
             phys.step();
 
-            let mut map: HashMap<u128, &mut Object> =
-                potion.objects.iter_mut().map(|o| (o.id as u128, o)).collect();
+            let mut map: HashMap<u64, &mut Object> =
+                potion.objects.iter_mut().map(|o| (o.id, o)).collect();
             phys.handle_collisions(&mut map).expect("collision");
             phys.project(potion);
+            potion.layers = fluid.layers(phys, potion);
+            let reacted = potion.react(rules);
+            let dissolved = potion.dissolve(phys.dt());
+            let boiled = potion.boil(phys.dt());
+            if potion.soak(phys.dt()) || reacted || dissolved || boiled {
+                phys.sync(potion);
+            }
+        }
+    }
+
+    /// Mix the top two liquids in a vial. Return false if they can't be
+    /// mixed.
+    fn mix(&mut self, vial: usize, palette: &mut Palette) -> bool {
+        let fluid: &mut dyn FluidBackend = match &mut self.particles {
+            Some(particles) => &mut particles[vial],
+            None => &mut Layered,
+        };
+        fluid.mix(&mut self.vial_physics[vial], &mut self.potions[vial], palette)
+    }
+
+    /// Start pouring from one vial into another, measuring out the typed
+    /// amount if there is one. Return false if there is nothing to
+    /// animate: nothing can be poured, the amount isn't on one of the
+    /// source vial's marks, or liquid particles were tipped in to fall
+    /// by themselves.
+    fn pour(&mut self, from: usize, into: usize) -> bool {
+        let transfer = match self.amount.take() {
+            Some(amount) if !solver::is_measurable(&self.potions[from], amount) => None,
+            Some(amount) => self.potions[from].pour_amount(&self.potions[into], ml(amount as f32)),
+            None => self.potions[from].pour(&self.potions[into]),
+        };
+        let Some(transfer) = transfer else {
+            return false;
+        };
+        if let (Some(particles), Transfer::Liquid | Transfer::LiquidAmount(_)) = (&mut self.particles, &transfer) {
+            let volume = match transfer {
+                Transfer::LiquidAmount(volume) => volume,
+                _ => self.potions[from].layers.last().map_or(ml(0.0), Layer::volume),
+            };
+            let (Ok([a, b]), Ok([physics_a, physics_b])) =
+                (particles.get_disjoint_mut([from, into]), self.vial_physics.get_disjoint_mut([from, into]))
+            else {
+                return false;
+            };
+            if a.pour(physics_a, b, physics_b, &self.potions[into], volume) > ml(0.0) {
+                self.moves.pours += 1;
+            }
+            return false;
         }
+        let start = (self.potions[from].clone(), self.potions[into].clone());
+        self.transfer = Some((transfer, 0.0, start));
+        self.moves.pours += 1;
+        true
     }
 
-    fn ui(&self, frame: &mut Frame, state: &AppState, popup: &Popup) {
+    /// The vial drawn at a cell and the point in it (units: mm).
+    fn hit(&self, column: u16, row: u16) -> Option<(usize, Vec2)> {
+        let palette = &self.levels[self.level_index].palette;
+        self.vial_areas
+            .iter()
+            .zip(&self.potions)
+            .enumerate()
+            .find_map(|(i, (area, vial))| {
+                tui::VialWidget::new(vial, palette)
+                    .to_vial(*area, column, row)
+                    .map(|pos| (i, pos))
+            })
+    }
+
+    /// Describe the object nearest to `pos` in a vial, if there is one close by.
+    fn inspect(&self, vial: usize, pos: Vec2) -> Option<String> {
+        let object = self.potions[vial]
+            .objects
+            .iter()
+            .filter(|o| o.pos.distance(pos) <= o.size.mm() + INSPECT_RADIUS)
+            .min_by(|a, b| a.pos.distance(pos).total_cmp(&b.pos.distance(pos)))?;
+        Some(format!(
+            "{:?} #{}\nsize {:.1} mm\nat ({:.1}, {:.1}) mm\n{:?}",
+            object.kind,
+            object.id,
+            object.size.mm(),
+            object.pos.x,
+            object.pos.y,
+            object.flags,
+        ))
+    }
+
+    fn ui(&mut self, frame: &mut Frame, state: &AppState, popup: &Popup, palette: &Palette) {
         match state {
-            AppState::Game | AppState::Transfer => self.render_game(frame),
+            AppState::LevelSelect => self.render_level_select(frame),
+            AppState::Game | AppState::Transfer => self.render_game(frame, palette),
             // AppState::GotoLevel => {
             AppState::End => {
-                self.render_game(frame);
+                self.render_game(frame, palette);
                 let rect = centered_rect(frame.size(), 35, 35);
                 frame.render_widget(Clear, rect);
                 frame.render_widget(
@@ -366,43 +685,51 @@ impl App {
             }
             _ => {}
         }
-        match popup {
-            Popup::Message(s) => {
-                self.render_game(frame);
-                let rect = centered_rect(frame.size(), 35, 35);
-                frame.render_widget(Clear, rect);
-                frame.render_widget(
-                    Paragraph::new(s.clone())
-                        .block(Block::default().borders(Borders::all()))
-                        .alignment(Alignment::Center),
-                    rect,
-                )
-            }
-            _ => ()
+        if let Popup::Message(s) | Popup::Inspect(s) = popup {
+            self.render_game(frame, palette);
+            let rect = centered_rect(frame.size(), 35, 35);
+            frame.render_widget(Clear, rect);
+            frame.render_widget(
+                Paragraph::new(s.clone())
+                    .block(Block::default().borders(Borders::all()))
+                    .alignment(Alignment::Center),
+                rect,
+            )
         }
     }
 
     fn on_tick(&mut self, state: &State<AppState>, next_state: &mut NextState<AppState>) {
         self.tick_count += 1;
+        while let Ok((index, par)) = self.par_output.try_recv() {
+            self.solving.remove(&index);
+            self.pars.insert(index, par);
+            if index == self.level_index {
+                self.par = par;
+            }
+        }
         let mut sync = vec![];
         match state.get() {
             AppState::Transfer =>
-                if let Some((ref transfer, ref mut t)) = self.transfer.as_mut() {
+                if let Some((ref transfer, ref mut t, (ref pour_from, ref pour_into))) = self.transfer {
                     if let Some(i) = self.selected {
                         let j = self.cursor;
-                        let pour_from = &self.potions[i];
-                        let pour_into = &self.potions[j];
+                        *t = (*t + POUR_STEP).min(1.0);
                         if let Some((a, b)) = transfer.lerp(pour_from, pour_into, *t) {
                             self.potions[i] = a;
                             self.potions[j] = b;
                             sync.push(i);
                             sync.push(j);
                         } else {
-                            *t = 2.0;
+                            *t = 1.0;
                         }
-                        *t += 0.1;
                         if *t >= 1.0 {
+                            // The poured liquid lands in the middle and the
+                            // emptied vial sloshes back as it is set down.
+                            let center = self.potions[j].size.x / 2.0;
+                            self.vial_physics[j].splash(center, -POUR_SPLASH);
+                            self.vial_physics[i].splash(0.0, POUR_SPLASH);
                             self.selected = None;
+                            self.transfer = None;
                             next_state.set(AppState::Game);
                         }
                     }
@@ -415,6 +742,7 @@ impl App {
                     }
                 }
             }
+            AppState::LevelSelect => (),
             AppState::GotoLevel => (),
             AppState::End => (),
         }
@@ -423,38 +751,79 @@ impl App {
         }
         if matches!(state.get(), AppState::Game) {
             self.step();
-            if ! sync.is_empty() {
-                if self.levels[self.level_index]
+            if !self.completed
+                && self.levels[self.level_index]
                     .goal
                     .is_complete(&self.potions)
-                {
-                    if let Some(ScriptChannels { ref mut input, ..}) = self.channels {
-                        input.try_send(Input::GoalReached).expect("script send");
-
-                    }
-                    // self.level_index += 1;
-                    // next_state.set(AppState::GotoLevel);
-                }
+            {
+                self.complete_level();
+                // self.level_index += 1;
+                // next_state.set(AppState::GotoLevel);
             }
         }
     }
 
-    fn render_game(&self, frame: &mut Frame) {
-        let [title, content] =
-            Layout::vertical([Constraint::Length(1), Constraint::Percentage(100)])
+    fn render_level_select(&mut self, frame: &mut Frame) {
+        let [title, content, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Percentage(100),
+            Constraint::Length(1),
+        ])
+        .areas(frame.size());
+        frame.render_widget(
+            Paragraph::new("Select a level").alignment(Alignment::Center),
+            title,
+        );
+        frame.render_stateful_widget(
+            tui::LevelSelect {
+                levels: &self.levels,
+                profile: &self.profile,
+            },
+            content,
+            &mut self.level_select,
+        );
+        frame.render_widget(
+            Paragraph::new("j/k: move  enter: play  q: quit").alignment(Alignment::Center),
+            footer,
+        );
+    }
+
+    fn render_game(&mut self, frame: &mut Frame, palette: &Palette) {
+        let [title, content, footer] =
+            Layout::vertical([Constraint::Length(1), Constraint::Percentage(100), Constraint::Length(1)])
                 .areas(frame.size());
         let layout = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(std::iter::repeat(Constraint::Fill(1)).take(self.potions.len()))
+            .constraints(std::iter::repeat_n(Constraint::Fill(1), self.potions.len()))
             .margin(5)
             .flex(Flex::Center)
             .spacing(10);
+        let mut status = format!("Level {}  Moves {}", self.level_index + 1, self.moves.total());
+        if let Some(par) = self.par {
+            status += &format!("  Par {}", par);
+        }
+        if let Some(best) = self.profile.best(self.level_index) {
+            status += &format!("  Best {}", best);
+        }
+        if let Some(amount) = self.amount {
+            status += &format!("  Pour {}", amount);
+        }
         frame.render_widget(
-            Paragraph::new(format!("Level {}", self.level_index + 1)).alignment(Alignment::Center),
+            Paragraph::new(status).alignment(Alignment::Center),
             title,
         );
-        let palette = &self.levels[self.level_index].palette;
-
+        let amount = if self.levels[self.level_index].goal.allows_measuring() { "0-9: amount  " } else { "" };
+        let heat = if self.particles.is_none() { "i/I: heat/cool  " } else { "" };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "h/l: move  space: pick/pour  {amount}m: mix  {heat}r: restart  n/p: next/previous  esc: levels"
+            ))
+            .alignment(Alignment::Center),
+            footer,
+        );
+        // The two vials in a transfer are drawn by the pour animation.
+        let pour = self.transfer.as_ref().zip(self.selected);
+        let mut areas = vec![];
         for (i, rect) in layout.split(content).iter().enumerate() {
             let selected = self.selected.map(|x| x == i).unwrap_or(false);
 
@@ -464,8 +833,12 @@ impl App {
                 Constraint::Min(if selected { 2 } else { 1 }),
             ])
             .areas(*rect);
+            areas.push(potion);
 
-            frame.render_widget(tui::VialWidget(&self.potions[i], palette), potion);
+            if pour.is_none() || (Some(i) != self.selected && i != self.cursor) {
+                let waves = self.vial_physics.get(i).and_then(VialPhysics::waves);
+                frame.render_widget(tui::VialWidget::new(&self.potions[i], palette).waves(waves), potion);
+            }
 
             frame.render_widget(
                 Paragraph::new(if self.cursor == i { "/\\" } else { "" })
@@ -473,17 +846,32 @@ impl App {
                 footer,
             );
         }
+        if let Some(((transfer, t, (from, into)), i)) = pour {
+            frame.render_widget(
+                tui::PourWidget::new(
+                    transfer,
+                    (from, areas[i]),
+                    (into, areas[self.cursor]),
+                    palette,
+                    *t,
+                ),
+                content,
+            );
+        }
+        self.vial_areas = areas;
     }
 }
 
 fn init_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
     Terminal::new(CrosstermBackend::new(stdout()))
 }
 
 fn restore_terminal() -> io::Result<()> {
     disable_raw_mode()?;
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     Ok(())
 }
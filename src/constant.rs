@@ -2,7 +2,6 @@ pub const GRAVITY: f32 = 9.81; // m/s^2
 pub const WATER_DENSITY: f32 = 997.0; //kg/m^3
 pub const GLASS_DENSITY: f32 = 2_400.0; //kg/m^3
 pub const G_TO_KG: f32 = 1_000.0;
-pub const M_TO_MM: f32 = 1_000.0;
-pub const MM_TO_M: f32 = 0.001;
 pub const SPHERE_DRAG: f32 = 0.47;
 pub const CIRCLE_DRAG: f32 = 1.17;
+pub const ROOM_TEMPERATURE: f32 = 20.0; // °C
@@ -0,0 +1,406 @@
+//! How the liquid in a vial is simulated.
+//!
+//! [Layered] treats the liquid as flat layers that push on objects by
+//! buoyancy. [Particles] fills the physics world with small balls of liquid
+//! that flow, pour and push objects around themselves. Either way the
+//! liquid can be read back as [Layer]s so goals keep working.
+use crate::constant::ROOM_TEMPERATURE;
+use crate::units::{self, mm, ml, LengthExt, Volume};
+use crate::user_data::UserData;
+use crate::vial_physics::{to_mm, VialPhysics};
+use crate::{Layer, Liquid, Palette, Vial};
+use bevy_math::Vec2;
+use rapier2d::prelude::*;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Radius of a particle of liquid (units: mm).
+const PARTICLE_RADIUS: f32 = 1.25;
+/// Viscosity at which neighbouring particles share half their relative
+/// velocity each step (units: Pa s).
+const HALF_SMOOTHING_VISCOSITY: f32 = 0.1;
+/// Largest share of relative velocity removed between neighbours per step.
+const MAX_SMOOTHING: f32 = 0.5;
+
+/// Simulates the liquid in a vial alongside [VialPhysics].
+pub trait FluidBackend {
+    /// Add the liquid's forces on the bodies in `physics` before it steps.
+    fn add_forces(&mut self, physics: &mut VialPhysics, vial: &Vial);
+
+    /// The liquid as layers from the bottom up.
+    fn layers(&self, physics: &VialPhysics, vial: &Vial) -> Vec<Layer>;
+
+    /// Mix the top two liquids as [Vial::mix] does and update the vial's
+    /// layers. Return false if they can't be mixed.
+    fn mix(&mut self, physics: &mut VialPhysics, vial: &mut Vial, palette: &mut Palette) -> bool;
+}
+
+/// Flat layers as given by [Vial::layers].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Layered;
+
+impl FluidBackend for Layered {
+    fn add_forces(&mut self, physics: &mut VialPhysics, vial: &Vial) {
+        physics.add_buoyancy_forces(vial);
+    }
+
+    fn layers(&self, _physics: &VialPhysics, vial: &Vial) -> Vec<Layer> {
+        vial.layers.clone()
+    }
+
+    fn mix(&mut self, _physics: &mut VialPhysics, vial: &mut Vial, palette: &mut Palette) -> bool {
+        vial.mix(palette)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    handle: RigidBodyHandle,
+    id: usize,
+    liquid: Liquid,
+    volume: Volume,
+}
+
+/// Liquid as balls that collide with each other, the glass and objects.
+///
+/// Each particle carries a share of its layer's volume. Contacts keep the
+/// liquid from compressing and objects float on it by those contacts.
+/// Viscosity smooths the velocities of neighbouring particles.
+#[derive(Debug, Clone, Default)]
+pub struct Particles {
+    particles: Vec<Particle>,
+}
+
+impl Particles {
+    /// Fill `physics` with particles for each of the vial's layers.
+    pub fn new(physics: &mut VialPhysics, vial: &Vial) -> Self {
+        physics.stiffen_contacts();
+        let r = PARTICLE_RADIUS;
+        let center = vial.size.x / 2.0;
+        let mut particles = vec![];
+        for (layer, bottom, top) in vial.layer_heights().skip(vial.settled()) {
+            let mut positions = vec![];
+            let mut y = bottom.mm() + r;
+            let mut row = 0;
+            while y < top.mm() + r / 2.0 {
+                // Stagger the rows so they settle into a hexagonal packing.
+                let half = vial.width_at(mm(y)).mm() / 2.0 - r;
+                let stagger = if row % 2 == 0 { 0.0 } else { r / 2.0 };
+                let mut x = center - half + stagger;
+                while x <= center + half + 1e-3 {
+                    positions.push(Vec2::new(x, y));
+                    x += 2.0 * r;
+                }
+                y += 2.0 * r;
+                row += 1;
+            }
+            if positions.is_empty() {
+                positions.push(Vec2::new(center, (bottom.mm() + top.mm()) / 2.0));
+            }
+            let volume = layer.volume() / positions.len() as f64;
+            for pos in positions {
+                particles.push(Self::spawn(physics, pos, layer.id(), layer.liquid().copied().unwrap_or_default(), volume));
+            }
+        }
+        Particles { particles }
+    }
+
+    fn spawn(physics: &mut VialPhysics, pos: Vec2, id: usize, liquid: Liquid, volume: Volume) -> Particle {
+        // Each ball stands for the square it fills so the liquid weighs
+        // what it should.
+        let density = liquid.density * 4.0 / PI;
+        let handle = physics.insert_particle(pos, mm(PARTICLE_RADIUS), density, UserData::particle(id as u64));
+        Particle { handle, id, liquid, volume }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn volume(&self) -> Volume {
+        self.particles.iter().fold(ml(0.0), |sum, p| sum + p.volume)
+    }
+
+    /// Positions of the particles with their liquid's palette index (units:
+    /// mm).
+    pub fn positions<'a>(&'a self, physics: &'a VialPhysics) -> impl Iterator<Item = (Vec2, usize)> + 'a {
+        self.particles
+            .iter()
+            .filter_map(|p| physics.bodies().get(p.handle).map(|b| (to_mm(b.translation()), p.id)))
+    }
+
+    /// Pour up to `volume` from the top of this liquid into another vial,
+    /// or as much as fits in `into_vial`. The particles drop in from above
+    /// its rim. Return the volume poured.
+    pub fn pour(
+        &mut self,
+        physics: &mut VialPhysics,
+        into: &mut Particles,
+        into_physics: &mut VialPhysics,
+        into_vial: &Vial,
+        volume: Volume,
+    ) -> Volume {
+        let height = |p: &Particle| physics.bodies().get(p.handle).map_or(0.0, |b| b.translation().y);
+        self.particles.sort_by(|a, b| height(b).total_cmp(&height(a)));
+        let settled = into_vial.layers[..into_vial.settled()].iter().fold(ml(0.0), |sum, l| sum + l.volume());
+        let volume = units::min(volume, into_vial.max_volume - settled - into.volume());
+        let size = into_physics.size();
+        let r = PARTICLE_RADIUS;
+        let columns = ((size.x / (2.0 * r)) as usize).max(1);
+        let mut poured = ml(0.0);
+        let mut i = 0;
+        while i < self.particles.len() && poured + self.particles[i].volume <= volume + ml(1e-6) {
+            poured = poured + self.particles[i].volume;
+            i += 1;
+        }
+        for (n, particle) in self.particles.drain(..i).enumerate() {
+            physics.remove_body(particle.handle);
+            let pos = Vec2::new(
+                size.x / 2.0 + ((n % columns) as f32 - (columns - 1) as f32 / 2.0) * 2.0 * r,
+                size.y + r + (n / columns) as f32 * 2.0 * r,
+            );
+            into.particles.push(Self::spawn(into_physics, pos, particle.id, particle.liquid, particle.volume));
+        }
+        poured
+    }
+}
+
+impl FluidBackend for Particles {
+    fn add_forces(&mut self, physics: &mut VialPhysics, _vial: &Vial) {
+        // Objects float by their contacts with the particles.
+        for (_, body) in physics.bodies_mut().iter_mut() {
+            body.reset_forces(true);
+            body.set_linear_damping(0.0);
+        }
+        // Share velocity between neighbours, which is the viscosity of
+        // XSPH. Neighbours are found in a grid of cells a particle wide.
+        let reach = mm(4.0 * PARTICLE_RADIUS).m();
+        let cell = |p: &Vector<Real>| ((p.x / reach).floor() as i32, (p.y / reach).floor() as i32);
+        let bodies = physics.bodies();
+        let state: Vec<(Vector<Real>, Vector<Real>)> = self
+            .particles
+            .iter()
+            .map(|p| bodies.get(p.handle).map_or((Vector::zeros(), Vector::zeros()), |b| (*b.translation(), *b.linvel())))
+            .collect();
+        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, (pos, _)) in state.iter().enumerate() {
+            grid.entry(cell(pos)).or_default().push(i);
+        }
+        let mut velocities = vec![];
+        for (i, particle) in self.particles.iter().enumerate() {
+            let (pos, vel) = state[i];
+            let share = (MAX_SMOOTHING * particle.liquid.viscosity
+                / (particle.liquid.viscosity + HALF_SMOOTHING_VISCOSITY))
+                .min(MAX_SMOOTHING);
+            let (cx, cy) = cell(&pos);
+            let mut sum = Vector::zeros();
+            let mut weight = 0.0;
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for &j in grid.get(&(cx + dx, cy + dy)).into_iter().flatten() {
+                        let d = (state[j].0 - pos).norm();
+                        if j != i && d < reach {
+                            let w = 1.0 - d / reach;
+                            sum += (state[j].1 - vel) * w;
+                            weight += w;
+                        }
+                    }
+                }
+            }
+            velocities.push(if weight > 0.0 { vel + sum / weight * share } else { vel });
+        }
+        let bodies = physics.bodies_mut();
+        for (particle, velocity) in self.particles.iter().zip(velocities) {
+            if let Some(body) = bodies.get_mut(particle.handle) {
+                body.set_linvel(velocity, false);
+            }
+        }
+    }
+
+    /// Each liquid becomes one layer at the temperature of its layer in
+    /// `vial`. Layers are ordered by the average height of their particles
+    /// and sit on the layers that were never turned into particles.
+    fn layers(&self, physics: &VialPhysics, vial: &Vial) -> Vec<Layer> {
+        let mut liquids: Vec<(usize, Liquid, Volume, f32, usize)> = vec![];
+        for particle in &self.particles {
+            let y = physics.bodies().get(particle.handle).map_or(0.0, |b| b.translation().y);
+            match liquids.iter_mut().find(|l| l.0 == particle.id) {
+                Some(l) => {
+                    l.2 = l.2 + particle.volume;
+                    l.3 += y;
+                    l.4 += 1;
+                }
+                None => liquids.push((particle.id, particle.liquid, particle.volume, y, 1)),
+            }
+        }
+        liquids.sort_by(|a, b| (a.3 / a.4 as f32).total_cmp(&(b.3 / b.4 as f32)));
+        let settled = &vial.layers[..vial.settled()];
+        let liquids = liquids.into_iter().map(|(id, liquid, volume, _, _)| {
+            let temperature = vial.layers.iter().find(|l| l.id() == id).map_or(ROOM_TEMPERATURE, Layer::temperature);
+            Layer::Liquid { id, volume, liquid, temperature }
+        });
+        settled.iter().cloned().chain(liquids).collect()
+    }
+
+    /// The particles of the top two liquids all take on their mix where
+    /// they are, so the two read back as one layer.
+    fn mix(&mut self, physics: &mut VialPhysics, vial: &mut Vial, palette: &mut Palette) -> bool {
+        let layers = self.layers(physics, vial);
+        let [.., a, b] = layers.as_slice() else {
+            return false;
+        };
+        let (a, b) = (a.id(), b.id());
+        let mut mixed = Vial { layers, ..Default::default() };
+        if !mixed.mix(palette) {
+            return false;
+        }
+        let Some(&Layer::Liquid { id, liquid, .. }) = mixed.layers.last() else {
+            return false;
+        };
+        for particle in self.particles.iter_mut().filter(|p| p.id == a || p.id == b) {
+            // Respawn the particle so that it weighs what the mix does.
+            let Some((pos, velocity)) = physics.bodies().get(particle.handle).map(|b| (to_mm(b.translation()), *b.linvel())) else {
+                continue;
+            };
+            physics.remove_body(particle.handle);
+            *particle = Self::spawn(physics, pos, id, liquid, particle.volume);
+            if let Some(body) = physics.bodies_mut().get_mut(particle.handle) {
+                body.set_linvel(velocity, true);
+            }
+        }
+        vial.layers = mixed.layers;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::units::VolumeExt;
+    use crate::{Goal, Object};
+
+    fn run(fluid: &mut impl FluidBackend, physics: &mut VialPhysics, vial: &mut Vial, steps: usize) {
+        for _ in 0..steps {
+            fluid.add_forces(physics, vial);
+            physics.step();
+            physics.project(vial);
+        }
+    }
+
+    #[test]
+    fn test_particle_layers() {
+        let mut vial = Vial {
+            layers: vec![
+                Layer::new(1, ml(15.0), Liquid::HONEY),
+                Layer::water(0, ml(10.0)),
+            ],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        let mut particles = Particles::new(&mut physics, &vial);
+        assert!(particles.len() > 20);
+        run(&mut particles, &mut physics, &mut vial, 120);
+        let layers = particles.layers(&physics, &vial);
+        assert_eq!(layers.iter().map(Layer::id).collect::<Vec<_>>(), vec![1, 0]);
+        assert!((layers[0].volume().ml() - 15.0).abs() < 1e-3);
+        assert!((layers[1].volume().ml() - 10.0).abs() < 1e-3);
+        // Nothing leaks out of the glass.
+        assert!(particles.positions(&physics).all(|(p, _)| p.y > 0.0 && p.x > 0.0 && p.x < vial.size.x));
+    }
+
+    #[test]
+    fn test_particle_pour() {
+        let mut a = Vial {
+            layers: vec![Layer::water(0, ml(20.0))],
+            ..Default::default()
+        };
+        let mut b = Vial {
+            layers: vec![Layer::water(1, ml(20.0))],
+            ..Default::default()
+        };
+        let (mut physics_a, mut physics_b) = (VialPhysics::new(&a), VialPhysics::new(&b));
+        let mut particles_a = Particles::new(&mut physics_a, &a);
+        let mut particles_b = Particles::new(&mut physics_b, &b);
+        let poured = particles_a.pour(&mut physics_a, &mut particles_b, &mut physics_b, &b, ml(20.0));
+        assert!((poured.ml() - 20.0).abs() < 1e-3);
+        assert!(particles_a.is_empty());
+        run(&mut particles_b, &mut physics_b, &mut b, 120);
+        b.layers = particles_b.layers(&physics_b, &b);
+        // The two liquids are now in one vial so it is not unmixed.
+        assert_eq!(b.layers.len(), 2);
+        assert!((b.vol().ml() - 40.0).abs() < 1e-3);
+        assert!(!Goal::Unmix.is_complete(&[a.clone(), b.clone()]));
+        a.layers = particles_a.layers(&physics_a, &a);
+        assert!(a.layers.is_empty());
+    }
+
+    #[test]
+    fn test_particle_partial_pour() {
+        let water = |id, volume| Layer::water(id, ml(volume));
+        let a = Vial { layers: vec![water(0, 20.0)], ..Default::default() };
+        let b = Vial { layers: vec![water(1, 20.0)], max_volume: ml(30.0), ..Default::default() };
+        let (mut physics_a, mut physics_b) = (VialPhysics::new(&a), VialPhysics::new(&b));
+        let mut particles_a = Particles::new(&mut physics_a, &a);
+        let mut particles_b = Particles::new(&mut physics_b, &b);
+        // A measured pour takes no more than it asks for.
+        let poured = particles_a.pour(&mut physics_a, &mut particles_b, &mut physics_b, &b, ml(5.0));
+        assert!(poured <= ml(5.0 + 1e-3) && poured > ml(4.0), "{}", poured.ml());
+        assert!((particles_a.volume().ml() + poured.ml() - 20.0).abs() < 1e-3);
+        // Nor more than fits.
+        let poured = particles_a.pour(&mut physics_a, &mut particles_b, &mut physics_b, &b, ml(20.0));
+        assert!(particles_b.volume() <= ml(30.0 + 1e-3), "{}", poured.ml());
+        assert!(!particles_a.is_empty());
+    }
+
+    #[test]
+    fn test_particle_mix() {
+        use crate::level::rgb;
+        let mut vial = Vial {
+            layers: vec![
+                Layer::new(1, ml(15.0), Liquid::HONEY),
+                Layer::water(0, ml(10.0)),
+            ],
+            ..Default::default()
+        };
+        let mut palette = Palette::new(vec![rgb(255, 0, 0), rgb(0, 0, 255)]);
+        let mut physics = VialPhysics::new(&vial);
+        let mut particles = Particles::new(&mut physics, &vial);
+        run(&mut particles, &mut physics, &mut vial, 60);
+        assert!(particles.mix(&mut physics, &mut vial, &mut palette));
+        assert_eq!(palette.len(), 3);
+        // The particles read back as the one mixed layer, and stay mixed.
+        run(&mut particles, &mut physics, &mut vial, 60);
+        let layers = particles.layers(&physics, &vial);
+        assert_eq!(layers.iter().map(Layer::id).collect::<Vec<_>>(), vec![2]);
+        assert!((layers[0].volume().ml() - 25.0).abs() < 1e-3);
+        assert!(particles.positions(&physics).all(|(_, id)| id == 2));
+        // There is nothing left to mix it with.
+        vial.layers = layers;
+        assert!(!particles.mix(&mut physics, &mut vial, &mut palette));
+    }
+
+    #[test]
+    fn test_object_floats_on_particles() {
+        let mut vial = Vial {
+            layers: vec![Layer::new(0, ml(20.0), Liquid::HONEY)],
+            objects: vec![Object {
+                pos: Vec2::new(12.5, 10.0),
+                size: mm(2.0),
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        // Keep the object clear of the particles it starts among.
+        vial.objects[0].pos.y = vial.surface().mm() + 10.0;
+        let mut physics = VialPhysics::new(&vial);
+        let mut particles = Particles::new(&mut physics, &vial);
+        run(&mut particles, &mut physics, &mut vial, 200);
+        let surface = particles.positions(&physics).map(|(p, _)| p.y).fold(0.0, f32::max);
+        assert!(vial.objects[0].pos.y > surface - 2.0 * PARTICLE_RADIUS, "{} {}", vial.objects[0].pos, surface);
+    }
+}
@@ -3,8 +3,13 @@ use bevy_math::Vec2;
 use derived_deref::{Deref, DerefMut};
 use kolorwheel::{HslColor, KolorWheel, RgbColor, SpinMode};
 use serde::{Deserialize, Serialize};
-use std::collections::BinaryHeap;
-use crate::user_data::{UserData};
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use bevy_log::warn;
+use crate::units::{self, ml, mm, Volume};
 
 #[derive(Debug, Clone, Deref, DerefMut, Deserialize, Serialize)]
 pub struct Palette(pub Vec<Color>);
@@ -23,9 +28,7 @@ impl Palette {
     }
 
     pub fn color(&self, layer: &Layer) -> Color {
-        match layer {
-            Layer::Liquid { id, .. } => self.0[*id].clone(),
-        }
+        self.0[layer.id()].clone()
     }
 
     pub fn from_seed<T: Into<HslColor>>(color: T, count: usize) -> Self {
@@ -39,36 +42,181 @@ impl Palette {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Level {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub palette: Palette,
     pub potions: Vec<Vial>,
     pub goal: Goal,
+    /// Fewest moves to complete the level. Computed by the solver if absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub par: Option<usize>,
+    /// What happens when objects touch.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
 }
 
 impl Default for Level {
     fn default() -> Self {
         Self {
+            title: None,
             palette: Palette::new(vec![rgb(255, 0, 0), rgb(0, 255, 0), rgb(0, 0, 255)]),
             potions: vec![],
             goal: Goal::Unmix,
+            par: None,
+            rules: vec![],
         }
     }
 }
 
 impl Level {
+    /// The level's title or a default based on its index.
+    pub fn title(&self, index: usize) -> String {
+        self.title.clone().unwrap_or_else(|| format!("Level {}", index + 1))
+    }
+
+    /// Check the level is playable.
+    pub fn validate(&self) -> Result<(), LevelError> {
+        if self.potions.is_empty() {
+            return Err(LevelError::NoVials);
+        }
+        let mut ids = HashSet::new();
+        for (i, vial) in self.potions.iter().enumerate() {
+            for (j, layer) in vial.layers.iter().enumerate() {
+                if layer.id() >= self.palette.len() {
+                    return Err(LevelError::UnknownColor { vial: i, id: layer.id() });
+                }
+                if layer.volume() <= ml(0.0) {
+                    return Err(LevelError::EmptyLayer { vial: i, layer: j });
+                }
+            }
+            if vial.vol() > vial.max_volume {
+                return Err(LevelError::Overfull { vial: i });
+            }
+            for (j, obj) in vial.objects.iter().enumerate() {
+                if obj.pos.x < 0.0 || obj.pos.x > vial.size.x || obj.pos.y < 0.0 || obj.pos.y > vial.size.y {
+                    return Err(LevelError::ObjectOutside { vial: i, object: j });
+                }
+                if !ids.insert(obj.id) {
+                    return Err(LevelError::DuplicateObjectId(obj.id));
+                }
+                for reaction in &obj.reactions {
+                    if let Effect::Dissolve { tint: Some(id), .. } = reaction.effect {
+                        if id >= self.palette.len() {
+                            return Err(LevelError::UnknownColor { vial: i, id });
+                        }
+                    }
+                }
+            }
+        }
+        match self.goal {
+            Goal::Amount { vial, .. }
+            | Goal::Measure { vial, .. }
+            | Goal::Boil { vial }
+            | Goal::Freeze { vial }
+                if vial >= self.potions.len() =>
+            {
+                return Err(LevelError::UnknownVial(vial));
+            }
+            Goal::Amount { vial, id, .. } if id >= self.palette.len() => {
+                return Err(LevelError::UnknownColor { vial, id });
+            }
+            _ => {}
+        }
+        if self.goal.is_complete(&self.potions) {
+            return Err(LevelError::AlreadyComplete);
+        }
+        Ok(())
+    }
+
     /// Return unique layer IDs.
-    #[allow(irrefutable_let_patterns)]
     pub fn layer_ids(vials: &[Vial]) -> impl Iterator<Item = usize> {
-        let heap: BinaryHeap<usize> = vials
+        let heap: BinaryHeap<usize> = vials.iter().flat_map(|v| &v.layers).map(Layer::id).collect();
+        heap.into_iter()
+    }
+
+    /// Things that are allowed but probably a mistake.
+    pub fn warnings(&self) -> Vec<LevelWarning> {
+        self.potions
             .iter()
-            .flat_map(|v| &v.layers)
-            .map(|l| {
-                let Layer::Liquid { id, .. } = l;
-                *id
+            .enumerate()
+            .filter(|(_, vial)| vial.physical)
+            .filter_map(|(i, vial)| {
+                let capacity = vial.capacity();
+                (units::abs_diff(vial.max_volume, capacity) > capacity * CAPACITY_TOLERANCE).then_some(
+                    LevelWarning::VolumeMismatch { vial: i, max_volume: vial.max_volume, capacity },
+                )
             })
-            .collect();
-        heap.into_iter()
+            .collect()
+    }
+
+    /// Load a level and log any warnings.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let level: Level = ron::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        for warning in level.warnings() {
+            warn!("{}: {}", path.display(), warning);
+        }
+        Ok(level)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LevelError {
+    NoVials,
+    UnknownColor { vial: usize, id: usize },
+    EmptyLayer { vial: usize, layer: usize },
+    Overfull { vial: usize },
+    ObjectOutside { vial: usize, object: usize },
+    DuplicateObjectId(u64),
+    UnknownVial(usize),
+    AlreadyComplete,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::NoVials => write!(f, "level has no vials"),
+            LevelError::UnknownColor { vial, id } => {
+                write!(f, "vial {} uses color {} which is not in the palette", vial, id)
+            }
+            LevelError::EmptyLayer { vial, layer } => {
+                write!(f, "vial {} layer {} has no volume", vial, layer)
+            }
+            LevelError::Overfull { vial } => write!(f, "vial {} holds more than its max volume", vial),
+            LevelError::ObjectOutside { vial, object } => {
+                write!(f, "vial {} object {} is outside the vial", vial, object)
+            }
+            LevelError::DuplicateObjectId(id) => write!(f, "object id {} is used more than once", id),
+            LevelError::UnknownVial(vial) => write!(f, "goal refers to vial {} which does not exist", vial),
+            LevelError::AlreadyComplete => write!(f, "goal is complete before any move"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+/// How far a physical vial's `max_volume` may be from its capacity, as a
+/// fraction of the capacity.
+const CAPACITY_TOLERANCE: f64 = 0.05;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LevelWarning {
+    /// A physical vial's `max_volume` does not fit its geometry.
+    VolumeMismatch { vial: usize, max_volume: Volume, capacity: Volume },
+}
+
+impl fmt::Display for LevelWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelWarning::VolumeMismatch { vial, max_volume, capacity } => write!(
+                f,
+                "vial {} has max volume {:.1} but its geometry holds {:.1}",
+                vial, max_volume, capacity
+            ),
+        }
     }
 }
 
@@ -76,18 +224,84 @@ impl Level {
 pub enum Goal {
     Unmix,
     BreakSeed,
+    /// Get `volume` of liquid `id` into a vial, give or take `tolerance`.
+    Amount {
+        vial: usize,
+        id: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(with = "units::serde::volume")]
+        tolerance: Volume,
+    },
+    /// Fill a vial to `volume`, give or take `tolerance`.
+    Measure {
+        vial: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(with = "units::serde::volume")]
+        tolerance: Volume,
+    },
+    /// Bring a liquid in a vial to a boil.
+    Boil { vial: usize },
+    /// Freeze all the liquid in a vial.
+    Freeze { vial: usize },
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Goal::Unmix => write!(f, "Unmix"),
+            Goal::BreakSeed => write!(f, "Break seed"),
+            Goal::Amount { vial, volume, .. } => write!(f, "Measure {} into vial {}", volume, vial + 1),
+            Goal::Measure { vial, volume, .. } => write!(f, "Fill vial {} to {}", vial + 1, volume),
+            Goal::Boil { vial } => write!(f, "Bring vial {} to a boil", vial + 1),
+            Goal::Freeze { vial } => write!(f, "Freeze vial {}", vial + 1),
+        }
+    }
 }
 
 impl Goal {
+    /// Whether the goal needs the vials heated or cooled.
+    pub fn needs_heat(&self) -> bool {
+        matches!(self, Goal::Boil { .. } | Goal::Freeze { .. })
+    }
+
+    /// Whether pours may be measured by typing an amount. Filling a vial to a
+    /// volume would be a single typed pour, so those levels go without.
+    pub fn allows_measuring(&self) -> bool {
+        !matches!(self, Goal::Measure { .. })
+    }
+
     pub fn is_complete(&self, potions: &[Vial]) -> bool {
         match self {
-            Goal::Unmix => potions.iter().all(|p| p.layers.len() <= 1),
+            Goal::Unmix => potions.iter().all(|p| p.layers.iter().filter(|l| l.liquid().is_some()).count() <= 1),
             Goal::BreakSeed => potions.iter().all(|p| {
                 p.objects
                     .iter()
                     .filter(|o| matches!(o.kind, ObjectKind::Seed))
-                    .map(|o| o.size)
-                    .all(|s| s <= 1.0)
+                    .all(|o| !o.can_break())
+            }),
+            Goal::Amount {
+                vial,
+                id,
+                volume,
+                tolerance,
+            } => potions.get(*vial).is_some_and(|p| {
+                let amount = p
+                    .layers
+                    .iter()
+                    .filter(|l| l.id() == *id)
+                    .fold(ml(0.0), |sum, l| sum + l.volume());
+                units::abs_diff(amount, *volume) <= *tolerance
+            }),
+            Goal::Measure {
+                vial,
+                volume,
+                tolerance,
+            } => potions.get(*vial).is_some_and(|p| units::abs_diff(p.vol(), *volume) <= *tolerance),
+            Goal::Boil { vial } => potions.get(*vial).is_some_and(|p| p.layers.iter().any(Layer::is_boiling)),
+            Goal::Freeze { vial } => potions.get(*vial).is_some_and(|p| {
+                p.layers.iter().any(|l| matches!(l, Layer::Frozen { .. })) && !p.layers.iter().any(Layer::is_liquid)
             }),
         }
     }
@@ -96,6 +310,7 @@ impl Goal {
 pub fn levels() -> Vec<Level> {
     vec![
         Level {
+            title: Some("Crack".into()),
             goal: Goal::BreakSeed,
             potions: vec![
                 Vial {
@@ -109,8 +324,8 @@ pub fn levels() -> Vec<Level> {
                         Object {
                             kind: ObjectKind::Seed,
                             pos: Vec2::new(10.0, 10.0),
-                            size: 2.0,
-                            id: UserData::object(1).into(),
+                            size: mm(2.0),
+                            id: 1,
                             ..Default::default()
                         },
                         // Object {
@@ -135,12 +350,7 @@ pub fn levels() -> Vec<Level> {
                     ..Default::default()
                 },
                 Vial {
-                    layers: vec![
-                        Layer::Liquid {
-                        id: 0,
-                        volume: 50.0,
-                    }
-                    ],
+                    layers: vec![Layer::water(0, ml(50.0))],
                     ..Default::default()
                 },
                 Vial {
@@ -151,105 +361,96 @@ pub fn levels() -> Vec<Level> {
             ..Default::default()
         },
         Level {
+            title: Some("Swap".into()),
             potions: vec![
                 Vial {
                     layers: vec![
-                        Layer::Liquid {
-                            id: 0,
-                            volume: 50.0,
-                        },
-                        Layer::Liquid {
-                            id: 1,
-                            volume: 50.0,
-                        },
+                        Layer::water(0, ml(50.0)),
+                        Layer::water(1, ml(50.0)),
                     ],
                     ..Default::default()
                 },
                 Vial {
-                    layers: vec![Layer::Liquid {
-                        id: 1,
-                        volume: 50.0,
-                    }],
+                    layers: vec![Layer::water(1, ml(50.0))],
                     ..Default::default()
                 },
             ],
             ..Default::default()
         },
         Level {
+            title: Some("Three colors".into()),
             potions: vec![
                 Vial {
                     layers: vec![
-                        Layer::Liquid {
-                            id: 0,
-                            volume: 50.0,
-                        },
-                        Layer::Liquid {
-                            id: 1,
-                            volume: 50.0,
-                        },
+                        Layer::water(0, ml(50.0)),
+                        Layer::water(1, ml(50.0)),
                     ],
                     ..Default::default()
                 },
                 Vial {
                     layers: vec![
-                        Layer::Liquid {
-                            id: 1,
-                            volume: 50.0,
-                        },
-                        Layer::Liquid {
-                            id: 2,
-                            volume: 25.0,
-                        },
+                        Layer::water(1, ml(50.0)),
+                        Layer::water(2, ml(25.0)),
                     ],
                     ..Default::default()
                 },
                 Vial {
-                    layers: vec![Layer::Liquid {
-                        id: 2,
-                        volume: 50.0,
-                    }],
+                    layers: vec![Layer::water(2, ml(50.0))],
                     ..Default::default()
                 },
             ],
             ..Default::default()
         },
         Level {
+            title: Some("Layer cake".into()),
             potions: vec![
                 Vial {
                     layers: vec![
-                        Layer::Liquid {
-                            id: 0,
-                            volume: 50.0,
-                        },
-                        Layer::Liquid {
-                            id: 1,
-                            volume: 25.0,
-                        },
-                        Layer::Liquid {
-                            id: 2,
-                            volume: 25.0,
-                        },
+                        Layer::water(0, ml(50.0)),
+                        Layer::water(1, ml(25.0)),
+                        Layer::water(2, ml(25.0)),
                     ],
                     ..Default::default()
                 },
                 Vial {
                     layers: vec![
-                        Layer::Liquid {
-                            id: 1,
-                            volume: 50.0,
-                        },
-                        Layer::Liquid {
-                            id: 2,
-                            volume: 25.0,
-                        },
+                        Layer::water(1, ml(50.0)),
+                        Layer::water(2, ml(25.0)),
                     ],
                     ..Default::default()
                 },
                 Vial {
-                    layers: vec![Layer::Liquid {
-                        id: 2,
-                        volume: 50.0,
-                    }],
+                    layers: vec![Layer::water(2, ml(50.0))],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+        Level {
+            title: Some("Jugs".into()),
+            goal: Goal::Measure {
+                vial: 1,
+                volume: ml(40.0),
+                tolerance: ml(0.5),
+            },
+            potions: vec![
+                Vial {
+                    layers: vec![Layer::water(0, ml(80.0))],
+                    max_volume: ml(80.0),
+                    size: Vec2::new(25.0, 60.0),
+                    graduation: Some(ml(10.0)),
+                    ..Default::default()
+                },
+                Vial {
+                    max_volume: ml(50.0),
+                    size: Vec2::new(25.0, 37.5),
+                    graduation: Some(ml(10.0)),
+                    ..Default::default()
+                },
+                Vial {
+                    max_volume: ml(30.0),
+                    size: Vec2::new(25.0, 22.5),
+                    graduation: Some(ml(10.0)),
                     ..Default::default()
                 },
             ],
@@ -257,3 +458,50 @@ pub fn levels() -> Vec<Level> {
         },
     ]
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for level in levels() {
+            assert_eq!(level.validate(), Ok(()));
+        }
+        let mut level = levels().remove(1);
+        level.potions[1].layers.push(Layer::water(2, ml(60.0)));
+        assert_eq!(level.validate(), Err(LevelError::Overfull { vial: 1 }));
+        level.potions[1].layers[1] = Layer::water(3, ml(10.0));
+        assert_eq!(level.validate(), Err(LevelError::UnknownColor { vial: 1, id: 3 }));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut level = levels().remove(1);
+        assert!(level.warnings().is_empty());
+        level.potions[1].physical = true;
+        let capacity = level.potions[1].capacity();
+        assert_eq!(
+            level.warnings(),
+            vec![LevelWarning::VolumeMismatch { vial: 1, max_volume: ml(100.0), capacity }]
+        );
+        level.potions[1].max_volume = capacity * 1.01;
+        assert!(level.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_amount_goal() {
+        let mut level = levels().remove(1);
+        level.goal = Goal::Amount { vial: 5, id: 0, volume: ml(30.0), tolerance: ml(1.0) };
+        assert_eq!(level.validate(), Err(LevelError::UnknownVial(5)));
+        level.potions[1].layers = vec![Layer::water(0, ml(29.5))];
+        level.goal = Goal::Amount { vial: 1, id: 0, volume: ml(30.0), tolerance: ml(1.0) };
+        assert!(level.goal.is_complete(&level.potions));
+        level.potions[1].layers = vec![Layer::water(0, ml(28.5))];
+        assert!(!level.goal.is_complete(&level.potions));
+        level.goal = Goal::Measure { vial: 1, volume: ml(30.0), tolerance: ml(1.0) };
+        assert!(!level.goal.is_complete(&level.potions));
+        level.potions[1].layers.push(Layer::water(1, ml(1.0)));
+        assert!(level.goal.is_complete(&level.potions));
+    }
+}
@@ -3,13 +3,33 @@ mod level;
 pub mod tui;
 mod vial;
 pub mod vial_physics;
+pub mod fluid;
 mod object;
+mod object_shape;
+mod shape;
+mod liquid;
+mod powder;
+mod material;
 pub mod user_data;
 pub mod script;
+mod rule;
+mod reaction;
+pub mod score;
+pub mod solver;
+pub mod units;
+mod wave;
 
-pub use level::{levels, Level, Palette};
+pub use level::{levels, Goal, Level, LevelError, LevelWarning, Palette};
 pub use vial::{Color, Layer, Lerp, Transfer, Transition, Vial, VialLoc};
-pub use object::{Object, ObjectKind, ObjectFlags, ByHeight};
+pub use object::{Object, ObjectKind, ObjectFlags, ByHeight, Fracture, Fragment};
+pub use object_shape::ObjectShape;
+pub use reaction::{Effect, Reaction};
+pub use rule::{Outcome, Rule};
+pub use shape::Shape;
+pub use liquid::Liquid;
+pub use powder::Powder;
+pub use material::Material;
+pub use wave::Waves;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,115 @@
+use crate::constant::WATER_DENSITY;
+use serde::{Deserialize, Serialize};
+
+/// Physical properties of a liquid.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Liquid {
+    /// Density (units: kg/m^3).
+    pub density: f32,
+    /// Dynamic viscosity (units: Pa s).
+    pub viscosity: f32,
+    /// Boiling point (units: °C).
+    #[serde(default = "water_boils")]
+    pub boils: f32,
+    /// Freezing point (units: °C).
+    #[serde(default = "water_freezes")]
+    pub freezes: f32,
+    /// Mass of powder dissolved in it, which is part of its density
+    /// (units: kg/m^3).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub dissolved: f32,
+}
+
+fn is_zero(x: &f32) -> bool {
+    *x == 0.0
+}
+
+fn water_boils() -> f32 {
+    Liquid::WATER.boils
+}
+
+fn water_freezes() -> f32 {
+    Liquid::WATER.freezes
+}
+
+impl Default for Liquid {
+    fn default() -> Self {
+        Liquid::WATER
+    }
+}
+
+impl Liquid {
+    pub const WATER: Liquid = Liquid {
+        density: WATER_DENSITY,
+        viscosity: 0.001,
+        boils: 100.0,
+        freezes: 0.0,
+        dissolved: 0.0,
+    };
+    pub const OIL: Liquid = Liquid {
+        density: 915.0,
+        viscosity: 0.08,
+        boils: 300.0,
+        freezes: -20.0,
+        dissolved: 0.0,
+    };
+    pub const HONEY: Liquid = Liquid {
+        density: 1_420.0,
+        viscosity: 10.0,
+        boils: 120.0,
+        freezes: -40.0,
+        dissolved: 0.0,
+    };
+    pub const ACID: Liquid = Liquid {
+        density: 1_180.0,
+        viscosity: 0.002,
+        boils: 110.0,
+        freezes: -30.0,
+        dissolved: 0.0,
+    };
+
+    pub(crate) fn is_water(&self) -> bool {
+        *self == Liquid::WATER
+    }
+
+    /// Whether this is close enough to `other` to count as the same
+    /// liquid, so a little of something else mixed in doesn't change it.
+    /// Powder dissolved in either is left out.
+    pub fn is_like(&self, other: &Liquid) -> bool {
+        let (density, other_density) = (self.density - self.dissolved, other.density - other.dissolved);
+        (density - other_density).abs() < 0.02 * other_density
+            && (self.viscosity / other.viscosity).ln().abs() < 0.5
+    }
+
+    /// Mix in `factor` of `other` by volume. Densities and boiling and
+    /// freezing points mix linearly and viscosities logarithmically, after
+    /// Arrhenius.
+    pub fn mix(&self, other: &Liquid, factor: f32) -> Liquid {
+        if self == other {
+            return *self;
+        }
+        let lerp = |a: f32, b: f32| a + (b - a) * factor;
+        Liquid {
+            density: lerp(self.density, other.density),
+            viscosity: lerp(self.viscosity.ln(), other.viscosity.ln()).exp(),
+            boils: lerp(self.boils, other.boils),
+            freezes: lerp(self.freezes, other.freezes),
+            dissolved: lerp(self.dissolved, other.dissolved),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mix() {
+        let half = Liquid::WATER.mix(&Liquid::HONEY, 0.5);
+        assert_eq!(half.density, (Liquid::WATER.density + Liquid::HONEY.density) / 2.0);
+        assert!((half.viscosity - 0.1).abs() < 1e-4);
+        let none = Liquid::WATER.mix(&Liquid::HONEY, 0.0);
+        assert_eq!(none.density, Liquid::WATER.density);
+        assert!((none.viscosity - Liquid::WATER.viscosity).abs() < 1e-9);
+    }
+}
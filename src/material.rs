@@ -0,0 +1,52 @@
+use crate::constant::{GLASS_DENSITY, WATER_DENSITY};
+use serde::{Deserialize, Serialize};
+
+/// Physical properties of what a vial or an object is made of.
+///
+/// Restitution multiplies with that of whatever it hits, so a bouncy object
+/// bounces less off wood than off metal.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Material {
+    /// Density (units: kg/m^3).
+    pub density: f32,
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::GLASS
+    }
+}
+
+impl Material {
+    pub const GLASS: Material = Material {
+        density: GLASS_DENSITY,
+        friction: 0.5,
+        restitution: 0.5,
+    };
+    pub const WOOD: Material = Material {
+        density: 700.0,
+        friction: 0.6,
+        restitution: 0.1,
+    };
+    pub const METAL: Material = Material {
+        density: 7_800.0,
+        friction: 0.4,
+        restitution: 0.8,
+    };
+    /// What seeds and other objects are made of unless told otherwise.
+    pub const SEED: Material = Material {
+        density: WATER_DENSITY,
+        friction: 0.5,
+        restitution: 0.7,
+    };
+
+    pub(crate) fn is_glass(&self) -> bool {
+        *self == Material::GLASS
+    }
+
+    pub(crate) fn is_seed(&self) -> bool {
+        *self == Material::SEED
+    }
+}
@@ -3,30 +3,240 @@ use derived_deref::{Deref};
 use std::cmp::Ordering;
 use bevy_math::Vec2;
 use serde::{Deserialize, Serialize};
+use crate::{Material, ObjectShape, Reaction};
+use crate::constant::ROOM_TEMPERATURE;
+use crate::units::{self, mm, Length, LengthExt};
+use crate::vial::{is_room_temperature, room_temperature};
 
 bitflags::bitflags! {
     #[derive(Debug, Clone, Deserialize, Serialize, Default)]
     pub struct ObjectFlags: u8 {
         const ENTER_VIAL = 0b00000001;
+        /// Dropped into an empty vial. The solver breaks it on landing
+        /// without simulating the fall.
         const EXPECT_BREAK = 0b00000010;
+        /// Hit something harder than its toughness and will break.
         const BREAK = 0b00000100;
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Object {
     pub kind: ObjectKind,
+    /// Center of the object (units: mm).
     pub pos: Vec2,
-    pub size: f32,
+    /// Radius of the object, or the scale of its shape.
+    #[serde(with = "units::serde::length")]
+    pub size: Length,
+    #[serde(default, skip_serializing_if = "ObjectShape::is_circle")]
+    pub shape: ObjectShape,
+    /// Counterclockwise turn of the shape (units: radians).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub angle: f32,
+    /// Identifies the object in its vial and the vial's physics. Ids are
+    /// unique within a vial; see [Vial::next_object_id](crate::Vial::next_object_id).
     pub id: u64,
     pub flags: ObjectFlags,
+    /// Largest impulse per unit mass from one contact that the object
+    /// survives (units: m/s).
+    #[serde(default = "default_toughness", skip_serializing_if = "is_default_toughness")]
+    pub toughness: f32,
+    #[serde(default = "default_material", skip_serializing_if = "Material::is_seed")]
+    pub material: Material,
+    /// How the object breaks. If none it breaks as its kind does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fracture: Option<Fracture>,
+    /// Velocity (units: mm/s).
+    #[serde(skip)]
+    pub velocity: Vec2,
+    /// How the object reacts to the liquid it sits in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    /// How long the object has sat in a liquid it reacts to (units: s).
+    #[serde(skip)]
+    pub soaked: f32,
+    /// Temperature (units: °C). It takes on that of the liquid around it.
+    #[serde(default = "room_temperature", skip_serializing_if = "is_room_temperature")]
+    pub temperature: f32,
+    /// The object this one has wrapped around and moves with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stuck_to: Option<u64>,
+    /// Objects this one has come into contact with since the vial last
+    /// applied its level's rules.
+    #[serde(skip)]
+    pub touching: Vec<u64>,
+}
+
+/// Toughness of a seed. Seeds dropped into an empty vial break; seeds
+/// dropped into deep liquid don't.
+pub const SEED_TOUGHNESS: f32 = 1.0;
+
+fn default_toughness() -> f32 {
+    SEED_TOUGHNESS
+}
+
+fn is_default_toughness(toughness: &f32) -> bool {
+    *toughness == SEED_TOUGHNESS
+}
+
+fn is_zero(x: &f32) -> bool {
+    *x == 0.0
+}
+
+fn default_material() -> Material {
+    Material::SEED
+}
+
+/// A piece an object breaks into.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Fragment {
+    pub kind: ObjectKind,
+    /// Share of the object's mass. Shares are taken relative to their sum.
+    pub share: f32,
+    /// Density (units: kg/m^3). If none it is that of the object's
+    /// material.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<f32>,
+}
+
+/// How an object breaks.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Fracture {
+    pub fragments: Vec<Fragment>,
+    /// Speed at which the fragments fly apart (units: mm/s).
+    pub scatter: f32,
+}
+
+impl Fracture {
+    /// Two equal halves of the same kind.
+    pub fn halves(kind: ObjectKind) -> Self {
+        let half = Fragment { kind, share: 0.5, density: None };
+        Fracture { fragments: vec![half.clone(), half], scatter: 100.0 }
+    }
+
+    /// How an object of `kind` breaks unless told otherwise. Seeds split
+    /// into a shell that sinks and a kernel that floats.
+    pub fn of(kind: ObjectKind) -> Self {
+        match kind {
+            ObjectKind::Seed => Fracture {
+                fragments: vec![
+                    Fragment { kind: ObjectKind::Shell, share: 0.6, density: Some(1_300.0) },
+                    Fragment { kind: ObjectKind::Kernel, share: 0.4, density: Some(800.0) },
+                ],
+                scatter: 150.0,
+            },
+            kind => Fracture::halves(kind),
+        }
+    }
+}
+
+impl Object {
+    /// Whether the object is big enough to break in two and isn't itself
+    /// a fragment.
+    pub fn can_break(&self) -> bool {
+        self.size > mm(1.0) && self.toughness.is_finite()
+    }
+
+    /// Mass (units: kg per m of depth).
+    pub fn mass(&self) -> f32 {
+        self.material.density * self.shape.area(self.size.m())
+    }
+
+    /// Distance from the center to the farthest point of the object.
+    pub fn extent(&self) -> Length {
+        mm(self.shape.extent(self.size.mm()))
+    }
+
+    /// Whether the point `p` (units: mm) is inside the object.
+    pub fn contains(&self, p: Vec2) -> bool {
+        self.shape.contains(self.size.mm(), self.angle, p - self.pos)
+    }
+
+    /// Outline of the object as a counterclockwise polygon (units: mm).
+    pub fn outline(&self) -> Vec<Vec2> {
+        self.shape
+            .outline(self.size.mm(), self.angle)
+            .into_iter()
+            .map(|p| p + self.pos)
+            .collect()
+    }
+
+    /// The fragments the object breaks into. They keep its mass, sit side
+    /// by side on its bottom without overlapping and fly apart from its
+    /// center. Fragments don't break again, so a break always ends the
+    /// same way. Their ids are left for the caller to assign.
+    pub fn fragments(&self) -> Vec<Object> {
+        let fracture = self.fracture.clone().unwrap_or_else(|| Fracture::of(self.kind));
+        let total: f32 = fracture.fragments.iter().map(|f| f.share).sum();
+        if total <= 0.0 {
+            return vec![];
+        }
+        let mut pieces: Vec<Object> = fracture
+            .fragments
+            .iter()
+            .map(|f| {
+                let density = f.density.unwrap_or(self.material.density);
+                // Area goes as mass over density and the radius as its root.
+                let size = self.size * (f.share / total * self.material.density / density).sqrt() as f64;
+                Object {
+                    kind: f.kind,
+                    size,
+                    shape: self.shape.clone(),
+                    angle: self.angle,
+                    material: Material { density, ..self.material },
+                    toughness: f32::INFINITY,
+                    reactions: self.reactions.clone(),
+                    temperature: self.temperature,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let width: f32 = pieces.iter().map(|o| 2.0 * o.extent().mm()).sum();
+        let bottom = self
+            .outline()
+            .iter()
+            .map(|p| p.y)
+            .fold(f32::INFINITY, f32::min);
+        let mut x = self.pos.x - width / 2.0;
+        for piece in &mut pieces {
+            let r = piece.extent().mm();
+            piece.pos = Vec2::new(x + r, bottom + r);
+            x += 2.0 * r;
+            let out = (piece.pos - self.pos).try_normalize().unwrap_or(Vec2::Y);
+            piece.velocity = self.velocity + out * fracture.scatter;
+        }
+        pieces
+    }
+}
+
+impl Default for Object {
+    fn default() -> Self {
+        Self {
+            kind: ObjectKind::default(),
+            pos: Vec2::ZERO,
+            size: mm(0.0),
+            shape: ObjectShape::Circle,
+            angle: 0.0,
+            id: 0,
+            flags: ObjectFlags::default(),
+            toughness: SEED_TOUGHNESS,
+            material: Material::SEED,
+            fracture: None,
+            velocity: Vec2::ZERO,
+            reactions: vec![],
+            soaked: 0.0,
+            temperature: ROOM_TEMPERATURE,
+            stuck_to: None,
+            touching: vec![],
+        }
+    }
 }
 
 #[derive(Deref)]
 pub struct ByHeight<'a>(pub usize, #[target] pub &'a Object);
 
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl<'a> PartialOrd for ByHeight<'a> {
-    #[allow(clippy::non_canonical_partial_ord_impl)]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.pos.y.partial_cmp(&other.pos.y)
     }
@@ -50,10 +260,14 @@ impl<'a> PartialEq for ByHeight<'a> {
 
 impl<'a> Eq for ByHeight<'a> {}
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize, Default)]
 pub enum ObjectKind {
     #[default]
     Seed,
     Creature,
     Plant,
+    /// The hard outside of a broken seed.
+    Shell,
+    /// The inside of a broken seed.
+    Kernel,
 }
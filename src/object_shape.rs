@@ -0,0 +1,187 @@
+use bevy_math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Segments used to approximate each round end of a capsule.
+const CAP_SEGMENTS: usize = 8;
+
+/// The outline of an object. Lengths are multiples of the object's size so
+/// that its fragments keep their proportions.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub enum ObjectShape {
+    /// A circle `size` in radius.
+    #[default]
+    Circle,
+    /// A rod `size` in radius whose straight part runs `half_length` either
+    /// side of the center along x.
+    Capsule { half_length: f32 },
+    /// A rectangle with half extents `half` along x and y.
+    Box { half: Vec2 },
+    /// A convex polygon with its points around the center.
+    Polygon(Vec<Vec2>),
+}
+
+impl ObjectShape {
+    pub(crate) fn is_circle(&self) -> bool {
+        matches!(self, ObjectShape::Circle)
+    }
+
+    /// Area of the shape at `size` (units: mm^2 for a size in mm).
+    pub fn area(&self, size: f32) -> f32 {
+        let s2 = size * size;
+        match self {
+            ObjectShape::Circle => PI * s2,
+            ObjectShape::Capsule { half_length } => (PI + 4.0 * half_length) * s2,
+            ObjectShape::Box { half } => 4.0 * half.x * half.y * s2,
+            ObjectShape::Polygon(points) => polygon_area(points).abs() * s2,
+        }
+    }
+
+    /// Distance from the center to the farthest point of the shape.
+    pub fn extent(&self, size: f32) -> f32 {
+        match self {
+            ObjectShape::Circle => size,
+            ObjectShape::Capsule { half_length } => (1.0 + half_length) * size,
+            ObjectShape::Box { half } => half.length() * size,
+            ObjectShape::Polygon(points) => {
+                points.iter().map(|p| p.length()).fold(0.0, f32::max) * size
+            }
+        }
+    }
+
+    /// Outline as a polygon counterclockwise around the origin at `size`,
+    /// turned by `angle` (units: radians). Round parts are approximated.
+    pub fn outline(&self, size: f32, angle: f32) -> Vec<Vec2> {
+        let arc = |center: Vec2, from: f32, segments: usize, sweep: f32| {
+            (0..=segments).map(move |i| {
+                let t = from + sweep * i as f32 / segments as f32;
+                center + Vec2::new(t.cos(), t.sin())
+            })
+        };
+        let points: Vec<Vec2> = match self {
+            ObjectShape::Circle => arc(Vec2::ZERO, 0.0, 4 * CAP_SEGMENTS, 2.0 * PI)
+                .skip(1)
+                .collect(),
+            ObjectShape::Capsule { half_length } => {
+                let c = Vec2::new(*half_length, 0.0);
+                arc(c, -PI / 2.0, CAP_SEGMENTS, PI)
+                    .chain(arc(-c, PI / 2.0, CAP_SEGMENTS, PI))
+                    .collect()
+            }
+            ObjectShape::Box { half } => vec![
+                Vec2::new(half.x, -half.y),
+                *half,
+                Vec2::new(-half.x, half.y),
+                -*half,
+            ],
+            ObjectShape::Polygon(points) if polygon_area(points) < 0.0 => {
+                points.iter().rev().copied().collect()
+            }
+            ObjectShape::Polygon(points) => points.clone(),
+        };
+        let turn = Vec2::from_angle(angle);
+        points.into_iter().map(|p| turn.rotate(p * size)).collect()
+    }
+
+    /// Whether `p`, relative to the center, is inside the shape at `size`
+    /// turned by `angle` (units: radians).
+    pub fn contains(&self, size: f32, angle: f32, p: Vec2) -> bool {
+        let p = Vec2::from_angle(-angle).rotate(p) / size;
+        match self {
+            ObjectShape::Circle => p.length() <= 1.0,
+            ObjectShape::Capsule { half_length } => {
+                Vec2::new(p.x.clamp(-half_length, *half_length), 0.0).distance(p) <= 1.0
+            }
+            ObjectShape::Box { half } => p.x.abs() <= half.x && p.y.abs() <= half.y,
+            ObjectShape::Polygon(_) => {
+                let points = self.outline(1.0, 0.0);
+                (0..points.len()).all(|i| {
+                    let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                    (b - a).perp_dot(p - a) >= 0.0
+                })
+            }
+        }
+    }
+}
+
+/// Signed area of a polygon, positive if it runs counterclockwise.
+pub(crate) fn polygon_area(points: &[Vec2]) -> f32 {
+    (0..points.len())
+        .map(|i| points[i].perp_dot(points[(i + 1) % points.len()]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Area of a counterclockwise polygon below the line at height `w` and the
+/// x of that part's centroid, or `None` if none of it is below.
+pub(crate) fn polygon_area_below(points: &[Vec2], w: f32) -> Option<(f32, f32)> {
+    let mut clipped = vec![];
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        if a.y <= w {
+            clipped.push(a);
+        }
+        if (a.y < w) != (b.y < w) {
+            let t = (w - a.y) / (b.y - a.y);
+            clipped.push(a + (b - a) * t);
+        }
+    }
+    let area = polygon_area(&clipped);
+    if area <= 0.0 {
+        return None;
+    }
+    let moment: f32 = (0..clipped.len())
+        .map(|i| {
+            let (a, b) = (clipped[i], clipped[(i + 1) % clipped.len()]);
+            (a.x + b.x) * a.perp_dot(b)
+        })
+        .sum::<f32>()
+        / 6.0;
+    Some((area, moment / area))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_areas() {
+        let capsule = ObjectShape::Capsule { half_length: 2.0 };
+        let outline = capsule.outline(1.0, 0.3);
+        assert!((polygon_area(&outline) - capsule.area(1.0)).abs() < 0.05 * capsule.area(1.0));
+        let square = ObjectShape::Box {
+            half: Vec2::splat(1.0),
+        };
+        assert_eq!(square.area(2.0), 16.0);
+        let triangle = ObjectShape::Polygon(vec![
+            Vec2::new(0.0, 1.0),
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+        ]);
+        assert_eq!(triangle.area(1.0), 2.0);
+        assert!(triangle.contains(1.0, 0.0, Vec2::ZERO));
+        assert!(!triangle.contains(1.0, 0.0, Vec2::new(0.9, 0.9)));
+        // A rod turned upright is tall and thin.
+        let rod = ObjectShape::Capsule { half_length: 3.0 };
+        assert!(rod.contains(1.0, PI / 2.0, Vec2::new(0.0, 3.5)));
+        assert!(!rod.contains(1.0, PI / 2.0, Vec2::new(3.5, 0.0)));
+    }
+
+    #[test]
+    fn test_polygon_area_below() {
+        let square = ObjectShape::Box {
+            half: Vec2::splat(1.0),
+        }
+        .outline(1.0, 0.0);
+        assert_eq!(polygon_area_below(&square, -2.0), None);
+        assert_eq!(polygon_area_below(&square, 0.0), Some((2.0, 0.0)));
+        assert_eq!(polygon_area_below(&square, 2.0), Some((4.0, 0.0)));
+        // Tilted, more of the lower side is under and the centroid moves.
+        let tilted = ObjectShape::Box {
+            half: Vec2::new(2.0, 0.5),
+        }
+        .outline(1.0, 0.3);
+        let (_, x) = polygon_area_below(&tilted, 0.0).unwrap();
+        assert!(x < 0.0);
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Physical properties of a powder. Powders sink below liquids and lie at
+/// the bottom of a vial.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Powder {
+    /// Density of the powder as it lies, gaps and all (units: kg/m^3).
+    pub density: f32,
+    /// Volume that dissolves each second into the liquid on top of it
+    /// (units: ml/s). Zero if it doesn't dissolve.
+    #[serde(default)]
+    pub dissolves: f32,
+}
+
+impl Default for Powder {
+    fn default() -> Self {
+        Powder::SAND
+    }
+}
+
+impl Powder {
+    pub const SAND: Powder = Powder {
+        density: 1_600.0,
+        dissolves: 0.0,
+    };
+    pub const SALT: Powder = Powder {
+        density: 1_200.0,
+        dissolves: 0.5,
+    };
+}
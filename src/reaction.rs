@@ -0,0 +1,45 @@
+use crate::units::{self, Length};
+use crate::{Liquid, ObjectKind};
+use serde::{Deserialize, Serialize};
+
+/// What an object does while it sits in a liquid.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Reaction {
+    /// The liquid it reacts to, or any like it. See [Liquid::is_like].
+    pub liquid: Liquid,
+    pub effect: Effect,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Effect {
+    /// Soak up liquid and swell at `rate` (units: mm/s) up to `max` in
+    /// size.
+    Swell {
+        rate: f32,
+        #[serde(with = "units::serde::length")]
+        max: Length,
+    },
+    /// Shrink at `rate` (units: mm/s) until gone. If `tint` is some, it is
+    /// a dye: as it dissolves it colors liquid from its layer in palette
+    /// color `tint`, more the larger it was. The dyed liquid lies on top of
+    /// the rest until mixed and counts as `tint` for goals such as
+    /// [Goal::Amount](crate::Goal::Amount).
+    Dissolve {
+        rate: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tint: Option<usize>,
+    },
+    /// Turn into `kind` after soaking for `after` (units: s).
+    Change { kind: ObjectKind, after: f32 },
+}
+
+impl Reaction {
+    pub fn new(liquid: Liquid, effect: Effect) -> Self {
+        Reaction { liquid, effect }
+    }
+
+    /// Whether the reaction happens in `liquid`.
+    pub fn applies(&self, liquid: &Liquid) -> bool {
+        liquid.is_like(&self.liquid)
+    }
+}
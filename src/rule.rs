@@ -0,0 +1,71 @@
+use crate::units::{mm, LengthExt};
+use crate::{Material, Object, ObjectKind};
+use serde::{Deserialize, Serialize};
+
+/// What happens when objects of two kinds touch in a vial.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Rule {
+    /// The kinds that react. The first acts on the second: it eats it or
+    /// wraps around it.
+    pub kinds: [ObjectKind; 2],
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Outcome {
+    /// Both become one object of this kind with their combined mass.
+    Merge(ObjectKind),
+    /// The first swallows the second and grows by its mass.
+    Eat,
+    /// The first holds on to the second and they move as one.
+    Wrap,
+}
+
+impl Rule {
+    /// Whether the rule applies to `a` touching `b`, and if so whether `b`
+    /// is the one that acts.
+    pub fn matches(&self, a: &Object, b: &Object) -> Option<bool> {
+        if [a.kind, b.kind] == self.kinds {
+            Some(false)
+        } else if [b.kind, a.kind] == self.kinds {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// One object of `kind` with the mass and momentum of `a` and `b`, at their
+/// center of mass, with id `id`. It takes the shape and toughness of `a`.
+pub(crate) fn merge(a: &Object, b: &Object, kind: ObjectKind, id: u64) -> Object {
+    let (mass_a, mass_b) = (a.mass(), b.mass());
+    let mass = mass_a + mass_b;
+    let area = a.shape.area(a.size.mm()) + b.shape.area(b.size.mm());
+    Object {
+        kind,
+        pos: (a.pos * mass_a + b.pos * mass_b) / mass,
+        velocity: (a.velocity * mass_a + b.velocity * mass_b) / mass,
+        size: mm((area / a.shape.area(1.0)).sqrt()),
+        shape: a.shape.clone(),
+        angle: a.angle,
+        material: Material {
+            density: mass / (area * 1e-6),
+            ..a.material
+        },
+        toughness: a.toughness,
+        reactions: a.reactions.clone(),
+        temperature: (a.temperature * mass_a + b.temperature * mass_b) / mass,
+        id,
+        ..Default::default()
+    }
+}
+
+/// Grow `eater` by the mass of `food` at its own density.
+pub(crate) fn eat(eater: &mut Object, food: &Object) {
+    let area = eater.shape.area(eater.size.mm());
+    if area <= 0.0 {
+        return;
+    }
+    let gained = food.mass() / eater.material.density * 1e6;
+    eater.size = mm(eater.size.mm() * ((area + gained) / area).sqrt());
+}
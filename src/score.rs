@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Moves the player made on a level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Moves {
+    pub pours: usize,
+    pub mixes: usize,
+    /// Times a vial was heated or cooled.
+    #[serde(default)]
+    pub heats: usize,
+    pub restarts: usize,
+}
+
+impl Moves {
+    /// Restarts count as a move so they cannot be used to dodge the par.
+    pub fn total(&self) -> usize {
+        self.pours + self.mixes + self.heats + self.restarts
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Score {
+    pub moves: Moves,
+    pub par: Option<usize>,
+    pub stars: u8,
+}
+
+impl Score {
+    pub fn new(moves: Moves, par: Option<usize>) -> Self {
+        Self {
+            moves,
+            par,
+            stars: stars(moves.total(), par),
+        }
+    }
+
+    /// Is this score better than `other`?
+    pub fn beats(&self, other: &Score) -> bool {
+        self.stars > other.stars
+            || (self.stars == other.stars && self.moves.total() < other.moves.total())
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stars = self.stars as usize;
+        write!(f, "{}{} {} moves", "★".repeat(stars), "☆".repeat(3 - stars), self.moves.total())?;
+        if let Some(par) = self.par {
+            write!(f, " (par {})", par)?;
+        }
+        Ok(())
+    }
+}
+
+/// Award 1–3 stars. Without a par every completion earns three stars.
+pub fn stars(moves: usize, par: Option<usize>) -> u8 {
+    match par {
+        Some(par) if moves > 2 * par => 1,
+        Some(par) if moves > par => 2,
+        _ => 3,
+    }
+}
+
+/// The player's best scores, persisted between runs.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Profile {
+    pub best: BTreeMap<usize, Score>,
+}
+
+impl Profile {
+    /// Load a profile. A missing file is an empty profile.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                ron::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    pub fn best(&self, level: usize) -> Option<&Score> {
+        self.best.get(&level)
+    }
+
+    /// The first level and any level after a completed one can be played.
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level == 0 || self.best.contains_key(&level) || self.best.contains_key(&(level - 1))
+    }
+
+    /// Record a score. Return true if it is a new best.
+    pub fn record(&mut self, level: usize, score: Score) -> bool {
+        match self.best.get(&level) {
+            Some(best) if !score.beats(best) => false,
+            _ => {
+                self.best.insert(level, score);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stars() {
+        assert_eq!(stars(2, Some(3)), 3);
+        assert_eq!(stars(3, Some(3)), 3);
+        assert_eq!(stars(4, Some(3)), 2);
+        assert_eq!(stars(6, Some(3)), 2);
+        assert_eq!(stars(7, Some(3)), 1);
+        assert_eq!(stars(7, None), 3);
+    }
+
+    #[test]
+    fn test_record() {
+        let mut profile = Profile::default();
+        let moves = |pours| Moves { pours, ..Default::default() };
+        assert!(profile.record(0, Score::new(moves(5), Some(2))));
+        assert!(profile.record(0, Score::new(moves(3), Some(2))));
+        assert!(!profile.record(0, Score::new(moves(4), Some(2))));
+        assert!(profile.record(0, Score::new(moves(2), Some(2))));
+        assert_eq!(profile.best(0).unwrap().stars, 3);
+        assert!(profile.is_unlocked(1));
+        assert!(!profile.is_unlocked(2));
+        let s = ron::to_string(&profile).unwrap();
+        let profile: Profile = ron::from_str(&s).unwrap();
+        assert_eq!(profile.best(0).unwrap().moves.total(), 2);
+    }
+}
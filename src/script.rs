@@ -2,10 +2,11 @@
 use async_channel::{Sender, Receiver};
 use std::time::Duration;
 use async_std::task;
+use crate::score::Score;
 
 pub enum Input {
     BrokeSeed,
-    GoalReached,
+    GoalReached(Score),
     Abort,
 }
 
@@ -15,17 +16,24 @@ pub enum Output {
 }
 
 pub async fn level_vanilla(level_number: usize, input: Receiver<Input>, output: Sender<Output>) {
-    loop {
-        let x = input.recv().await.unwrap();
-        if matches!(x, Input::GoalReached | Input::Abort) {
-            break;
+    let score = loop {
+        match input.recv().await {
+            Ok(Input::GoalReached(score)) => break score,
+            // The player left the level.
+            Ok(Input::Abort) | Err(_) => return,
+            _ => (),
         }
-    }
+    };
     task::sleep(Duration::from_millis(1000)).await;
     // We should add a wait here.
     output.send(Output::Message(format!("You passed level {}!!!", level_number))).await.unwrap();
     task::sleep(Duration::from_millis(1000)).await;
-    output.send(Output::Message("Good job".into())).await.unwrap();
+    let praise = match score.stars {
+        3 => "Perfect!",
+        2 => "Good job",
+        _ => "You made it. Can you do it in fewer moves?",
+    };
+    output.send(Output::Message(format!("{}\n{}", praise, score))).await.unwrap();
     task::sleep(Duration::from_millis(1000)).await;
     output.send(Output::End).await.unwrap();
 }
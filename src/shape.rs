@@ -0,0 +1,105 @@
+use bevy_math::Vec2;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// Segments used to approximate curved parts of a shape.
+const CURVE_SEGMENTS: usize = 8;
+
+/// Height of a flask's conical body as a fraction of the vial's height.
+const FLASK_BODY: f32 = 0.7;
+
+/// The outline of a vial, symmetric about its center line.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub enum Shape {
+    /// Straight sides and a flat bottom.
+    #[default]
+    Straight,
+    /// Straight sides and a half round bottom.
+    RoundBottom,
+    /// Narrows linearly from the top to a `bottom` fraction of the width.
+    Tapered { bottom: f32 },
+    /// A conical body narrowing to a straight neck `neck` fraction of the
+    /// width.
+    Flask { neck: f32 },
+    /// Width against height, both as fractions of the vial's size, from the
+    /// bottom up.
+    Profile(Vec<Vec2>),
+}
+
+impl Shape {
+    pub(crate) fn is_straight(&self) -> bool {
+        matches!(self, Shape::Straight)
+    }
+
+    /// Points of width (x) against height (y) for a vial of `size` (units:
+    /// mm). Heights increase from 0 to `size.y` and widths are linear in
+    /// between.
+    pub fn profile(&self, size: Vec2) -> Vec<Vec2> {
+        let points = match self {
+            Shape::Straight => vec![Vec2::new(size.x, 0.0), size],
+            Shape::RoundBottom => {
+                let r = (size.x / 2.0).min(size.y);
+                let mut points: Vec<Vec2> = (0..=CURVE_SEGMENTS)
+                    .map(|i| {
+                        let angle = FRAC_PI_2 * (i as f32 / CURVE_SEGMENTS as f32 - 1.0);
+                        Vec2::new(2.0 * r * angle.cos(), r + r * angle.sin())
+                    })
+                    .collect();
+                points.push(size);
+                points
+            }
+            Shape::Tapered { bottom } => vec![Vec2::new(size.x * bottom, 0.0), size],
+            Shape::Flask { neck } => vec![
+                Vec2::new(size.x, 0.0),
+                Vec2::new(size.x * neck, size.y * FLASK_BODY),
+                Vec2::new(size.x * neck, size.y),
+            ],
+            Shape::Profile(points) => points.iter().map(|p| *p * size).collect(),
+        };
+        let mut points: Vec<Vec2> = points
+            .into_iter()
+            .map(|p| p.clamp(Vec2::ZERO, size))
+            .collect();
+        points.sort_by(|a, b| a.y.total_cmp(&b.y));
+        // Make sure the profile spans the whole height.
+        match (points.first().copied(), points.last().copied()) {
+            (Some(first), Some(last)) => {
+                if first.y > 0.0 {
+                    points.insert(0, Vec2::new(first.x, 0.0));
+                }
+                if last.y < size.y {
+                    points.push(Vec2::new(last.x, size.y));
+                }
+                points
+            }
+            _ => Shape::Straight.profile(size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let size = Vec2::new(20.0, 60.0);
+        assert_eq!(Shape::Straight.profile(size), vec![Vec2::new(20.0, 0.0), size]);
+        let round = Shape::RoundBottom.profile(size);
+        assert_eq!(round.len(), CURVE_SEGMENTS + 2);
+        assert!(round[0].x.abs() < 1e-4 && round[0].y == 0.0);
+        assert!((round[CURVE_SEGMENTS] - Vec2::new(20.0, 10.0)).length() < 1e-4);
+        // Profiles are sorted and stretched to the full height.
+        let custom = Shape::Profile(vec![Vec2::new(1.0, 0.5), Vec2::new(0.5, 0.2)]).profile(size);
+        assert_eq!(
+            custom,
+            vec![
+                Vec2::new(10.0, 0.0),
+                Vec2::new(10.0, 12.0),
+                Vec2::new(20.0, 30.0),
+                Vec2::new(20.0, 60.0)
+            ]
+        );
+        assert_eq!(Shape::Profile(vec![]).profile(size), Shape::Straight.profile(size));
+    }
+}
@@ -0,0 +1,281 @@
+use super::{Level, ObjectFlags, ObjectKind, Palette, Transition, Vial, Lerp};
+use crate::units::{ml, LengthExt, VolumeExt};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// Give up on levels whose search space is larger than this.
+const MAX_STATES: usize = 100_000;
+
+/// Change in temperature from heating or cooling a vial once (units: °C).
+pub const HEAT_STEP: f32 = 20.0;
+
+/// A single player action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Pour { from: usize, into: usize },
+    /// Pour at most `amount` (units: ml), as typed before a pour.
+    PourAmount { from: usize, into: usize, amount: u32 },
+    Mix(usize),
+    Heat(usize),
+    Cool(usize),
+}
+
+/// Why [solve] found no solution.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolveError {
+    /// No sequence of moves completes the goal.
+    Unsolvable,
+    /// The search gave up after [MAX_STATES] states. A solution may still
+    /// exist.
+    SearchLimit,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Unsolvable => write!(f, "no solution exists"),
+            SolveError::SearchLimit => write!(f, "search limit of {} states reached", MAX_STATES),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Apply a move to the vials. Return false if the move is not possible.
+///
+/// The solver ignores physics and time: objects that would break on landing
+/// break immediately, everything settles to the floor of its vial and heat
+/// past boiling boils away at once.
+pub fn apply(m: Move, potions: &mut [Vial], palette: &mut Palette) -> bool {
+    match m {
+        Move::Pour { from, into } | Move::PourAmount { from, into, .. } => {
+            if from == into {
+                return false;
+            }
+            let transfer = match m {
+                Move::PourAmount { amount, .. } => potions[from].pour_amount(&potions[into], ml(amount as f32)),
+                _ => potions[from].pour(&potions[into]),
+            };
+            let Some(transfer) = transfer else {
+                return false;
+            };
+            let Some((a, b)) = transfer.lerp(&potions[from], &potions[into], 1.0) else {
+                return false;
+            };
+            potions[from] = a;
+            potions[into] = b;
+            settle(&mut potions[into]);
+            true
+        }
+        Move::Mix(i) => potions[i].mix(palette),
+        Move::Heat(i) => {
+            let heated = potions[i].heat(HEAT_STEP);
+            potions[i].boil(f32::INFINITY);
+            heated
+        }
+        Move::Cool(i) => potions[i].heat(-HEAT_STEP),
+    }
+}
+
+fn settle(vial: &mut Vial) {
+    for obj in &mut vial.objects {
+        if obj.flags.contains(ObjectFlags::EXPECT_BREAK) {
+            obj.flags.remove(ObjectFlags::EXPECT_BREAK);
+            obj.flags.insert(ObjectFlags::BREAK);
+        }
+        obj.flags.remove(ObjectFlags::ENTER_VIAL);
+    }
+    if let Some(Transition::BreakSeed(v) | Transition::MoveDown(v)) = vial.transition() {
+        *vial = v;
+    }
+    for obj in &mut vial.objects {
+        obj.pos.y = obj.size.mm();
+    }
+}
+
+/// The spacing of `vial`'s graduation marks (units: ml), if it has any.
+fn step(vial: &Vial) -> Option<u32> {
+    vial.graduation.map(|step| step.ml().round() as u32).filter(|step| *step > 0)
+}
+
+/// Whether `amount` (units: ml) can be measured out of `vial`: it must fall
+/// on one of the vial's graduation marks. The game only takes typed amounts
+/// that pass this, so it and the solver agree on the moves there are.
+pub fn is_measurable(vial: &Vial, amount: u32) -> bool {
+    step(vial).is_some_and(|step| amount > 0 && amount.is_multiple_of(step))
+}
+
+/// Amounts worth measuring out of `vial` (units: ml): each of its
+/// graduation marks below the top of its top layer. Pouring the whole layer
+/// or more is a plain [Move::Pour].
+fn amounts(vial: &Vial) -> Vec<u32> {
+    let (Some(step), Some(top)) = (step(vial), vial.layers.last()) else {
+        return vec![];
+    };
+    (1..).map(|i| i * step).take_while(|amount| (*amount as f32) < top.volume().ml()).collect()
+}
+
+type Key = Vec<(Vec<(usize, i64, i64)>, Vec<(ObjectKind, i64)>)>;
+
+fn quantize(x: f32) -> i64 {
+    (x * 100.0).round() as i64
+}
+
+fn key(potions: &[Vial]) -> Key {
+    potions
+        .iter()
+        .map(|v| {
+            let layers = v
+                .layers
+                .iter()
+                .map(|l| (l.id(), quantize(l.volume().ml()), quantize(l.temperature())))
+                .collect();
+            let mut objects: Vec<_> = v.objects.iter().map(|o| (o.kind, quantize(o.size.mm()))).collect();
+            objects.sort_unstable();
+            (layers, objects)
+        })
+        .collect()
+}
+
+/// Find a shortest sequence of moves that completes the level's goal.
+/// Measured pours are tried at each graduation of the vial poured from,
+/// unless the goal doesn't allow measuring.
+/// Heating and cooling are only tried for goals that need them.
+///
+/// Each state keeps its own palette so that a mix gets the same color
+/// index however it is reached.
+///
+/// Returns an error if the level cannot be solved or the search is too
+/// large to tell.
+pub fn solve(level: &Level) -> Result<Vec<Move>, SolveError> {
+    let mut potions = level.potions.clone();
+    for vial in &mut potions {
+        settle(vial);
+    }
+    let count = potions.len();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert(key(&potions));
+    queue.push_back((potions, level.palette.clone(), vec![]));
+    while let Some((potions, palette, moves)) = queue.pop_front() {
+        if level.goal.is_complete(&potions) {
+            return Ok(moves);
+        }
+        let pours = (0..count)
+            .flat_map(|from| (0..count).map(move |into| Move::Pour { from, into }));
+        let measured: Vec<Move> = (0..count)
+            .filter(|_| level.goal.allows_measuring())
+            .flat_map(|from| {
+                amounts(&potions[from])
+                    .into_iter()
+                    .flat_map(move |amount| (0..count).map(move |into| Move::PourAmount { from, into, amount }))
+            })
+            .collect();
+        let heats = (0..count)
+            .flat_map(|i| [Move::Heat(i), Move::Cool(i)])
+            .filter(|_| level.goal.needs_heat());
+        for m in pours.chain(measured).chain((0..count).map(Move::Mix)).chain(heats) {
+            let mut next = potions.clone();
+            let mut palette = palette.clone();
+            if !apply(m, &mut next, &mut palette) {
+                continue;
+            }
+            if seen.insert(key(&next)) {
+                if seen.len() > MAX_STATES {
+                    return Err(SolveError::SearchLimit);
+                }
+                let mut moves = moves.clone();
+                moves.push(m);
+                queue.push_back((next, palette, moves));
+            }
+        }
+    }
+    Err(SolveError::Unsolvable)
+}
+
+/// The fewest moves needed to complete the level, if known.
+pub fn par(level: &Level) -> Option<usize> {
+    level.par.or_else(|| solve(level).ok().map(|moves| moves.len()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_solve_levels() {
+        for level in crate::levels() {
+            let moves = solve(&level).expect("solution");
+            let mut potions = level.potions.clone();
+            let mut palette = level.palette.clone();
+            for vial in &mut potions {
+                settle(vial);
+            }
+            for m in moves {
+                assert!(apply(m, &mut potions, &mut palette));
+            }
+            assert!(level.goal.is_complete(&potions));
+        }
+    }
+
+    #[test]
+    fn test_par() {
+        let levels = crate::levels();
+        assert_eq!(par(&levels[1]), Some(1));
+        // The classic 8/5/3 jugs puzzle, without typed amounts.
+        assert!(!levels[4].goal.allows_measuring());
+        assert_eq!(par(&levels[4]), Some(6));
+    }
+
+    #[test]
+    fn test_solve_amount() {
+        use crate::{Goal, Layer};
+        let water = Layer::water(0, ml(50.0));
+        let jug = Vial { layers: vec![water], graduation: Some(ml(10.0)), ..Default::default() };
+        let level = Level {
+            potions: vec![jug, Vial::default()],
+            goal: Goal::Amount { vial: 1, id: 0, volume: ml(20.0), tolerance: ml(0.5) },
+            ..Default::default()
+        };
+        assert_eq!(solve(&level), Ok(vec![Move::PourAmount { from: 0, into: 1, amount: 20 }]));
+        assert!(is_measurable(&level.potions[0], 20));
+        assert!(!is_measurable(&level.potions[0], 25));
+        // Without marks there is nothing to measure by.
+        let mut level = level;
+        level.potions[0].graduation = None;
+        assert_eq!(solve(&level), Err(SolveError::Unsolvable));
+        assert!(!is_measurable(&level.potions[0], 20));
+    }
+
+    #[test]
+    fn test_solve_mix() {
+        // Mixing the last vial first gives its layer the first new color,
+        // however many other mixes the search has tried.
+        use crate::{level::rgb, units::ml, Goal, Layer};
+        let water = |id| Layer::water(id, ml(10.0));
+        let vial = Vial { layers: vec![water(0), water(1)], ..Default::default() };
+        let level = Level {
+            palette: Palette::new(vec![rgb(255, 0, 0), rgb(0, 0, 255)]),
+            potions: vec![vial.clone(), vial.clone(), vial],
+            goal: Goal::Amount { vial: 2, id: 2, volume: ml(20.0), tolerance: ml(0.1) },
+            ..Default::default()
+        };
+        assert_eq!(solve(&level), Ok(vec![Move::Mix(2)]));
+    }
+
+    #[test]
+    fn test_solve_heat() {
+        use crate::{units::ml, Goal, Layer};
+        let water = Layer::water(0, ml(30.0));
+        let level = Level {
+            potions: vec![Vial { layers: vec![water], ..Default::default() }, Vial::default()],
+            goal: Goal::Boil { vial: 0 },
+            ..Default::default()
+        };
+        assert_eq!(solve(&level), Ok(vec![Move::Heat(0); 4]));
+        let level = Level { goal: Goal::Freeze { vial: 1 }, ..level };
+        assert_eq!(solve(&level).map(|m| m.len()), Ok(2));
+        // Other goals leave the burner alone.
+        assert!(!Goal::Unmix.needs_heat());
+    }
+}
@@ -1,6 +1,8 @@
-use super::{Layer, ObjectKind, Palette, Vial};
+use super::{Layer, Lerp, Level, Object, ObjectKind, Palette, Transfer, Vial, Waves};
+use crate::score::Profile;
+use crate::units::{mm, LengthExt};
 use bevy_math::Vec2;
-use ratatui::prelude::*;
+use ratatui::{prelude::*, widgets::*};
 
 impl From<crate::Color> for Color {
     fn from(color: crate::Color) -> Self {
@@ -8,88 +10,722 @@ impl From<crate::Color> for Color {
     }
 }
 
+/// Draws a vial, its contents and its glass.
 #[derive(Debug, Clone)]
-pub struct VialWidget<'a>(pub &'a Vial, pub &'a Palette);
+pub struct VialWidget<'a> {
+    vial: &'a Vial,
+    palette: &'a Palette,
+    waves: Option<&'a Waves>,
+    cell_aspect: f32,
+    background: Color,
+}
+
+/// Pixel rows of glass between the top of the liquid and the rim.
+const NECK: u16 = 2;
+
+impl<'a> VialWidget<'a> {
+    pub fn new(vial: &'a Vial, palette: &'a Palette) -> Self {
+        Self {
+            vial,
+            palette,
+            waves: None,
+            cell_aspect: 2.0,
+            background: Color::Rgb(0, 0, 0),
+        }
+    }
+
+    /// Draw the surface of the liquid with these waves. Defaults to flat.
+    pub fn waves(mut self, waves: Option<&'a Waves>) -> Self {
+        self.waves = waves;
+        self
+    }
+
+    /// Height of a terminal cell divided by its width. Defaults to 2.
+    pub fn cell_aspect(mut self, cell_aspect: f32) -> Self {
+        self.cell_aspect = cell_aspect;
+        self
+    }
+
+    /// The color the glass is blended over. Defaults to black.
+    pub fn background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Fit the vial in `area` keeping the proportions of its size. Return
+    /// the area of the glass and where the inside of the vial is in it.
+    fn layout(&self, area: Rect) -> Option<(Rect, Interior)> {
+        let size = self.vial.size;
+        // A pixel is half a cell high.
+        let pixels_per_width = size.y / size.x * 2.0 / self.cell_aspect;
+        let max_height = (area.height * 2).checked_sub(NECK + 2)? as f32;
+        let width = ((area.width.checked_sub(4)? as f32).min(max_height / pixels_per_width)) as u16;
+        if width == 0 {
+            return None;
+        }
+        let height = ((width as f32 * pixels_per_width).round() as u16).clamp(1, max_height as u16);
+        let rows = height + NECK + 2;
+        let outer = Rect {
+            x: area.x + (area.width - width - 4) / 2,
+            y: area.y + area.height - rows.div_ceil(2),
+            width: width + 4,
+            height: rows.div_ceil(2),
+        };
+        let interior = Interior {
+            size,
+            x: 2,
+            y: outer.height * 2 - height - 1,
+            width,
+            height,
+        };
+        Some((outer, interior))
+    }
+
+    /// The area the vial is drawn in when rendered into `area`.
+    pub fn vial_area(&self, area: Rect) -> Rect {
+        self.layout(area).map(|(outer, _)| outer).unwrap_or_default()
+    }
+
+    /// The cell showing the point `pos` (units: mm) in the vial.
+    pub fn to_cell(&self, area: Rect, pos: Vec2) -> Option<(u16, u16)> {
+        let (area, interior) = self.layout(area)?;
+        let (x, y) = interior.to_pixel(pos);
+        Some((area.x + x, area.y + y / 2))
+    }
+
+    /// The point in the vial (units: mm) shown at the center of a cell, or
+    /// `None` if the cell is outside the glass. Points on the glass or
+    /// above the liquid are clamped to the vial's size.
+    pub fn to_vial(&self, area: Rect, column: u16, row: u16) -> Option<Vec2> {
+        let (area, interior) = self.layout(area)?;
+        if !(area.left()..area.right()).contains(&column)
+            || !(area.top()..area.bottom()).contains(&row)
+        {
+            return None;
+        }
+        let (x, y) = (column - area.x, (row - area.y) * 2);
+        let pos = (interior.to_vial(x, y) + interior.to_vial(x, y + 1)) / 2.0;
+        Some(pos.clamp(Vec2::ZERO, self.vial.size))
+    }
+}
+
+/// Share of white mixed into the color of a frozen layer.
+const FROST: f32 = 0.5;
+/// Share of white mixed into the color of a bubble in a boiling layer.
+const BUBBLE: f32 = 0.7;
+/// Share of black mixed into the color of a solid layer.
+const SHADE: f32 = 0.4;
+/// Share of black mixed into every other pixel of a powder layer.
+const GRAIN: f32 = 0.25;
+
+/// Mix `amount` of white into a color.
+fn lighten(color: Color, amount: f32) -> Color {
+    let mix = |a: u8| (a as f32 + (255.0 - a as f32) * amount).round() as u8;
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(mix(r), mix(g), mix(b)),
+        color => color,
+    }
+}
+
+/// Mix `amount` of black into a color.
+fn darken(color: Color, amount: f32) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(
+            (r as f32 * (1.0 - amount)).round() as u8,
+            (g as f32 * (1.0 - amount)).round() as u8,
+            (b as f32 * (1.0 - amount)).round() as u8,
+        ),
+        color => color,
+    }
+}
+
+/// Blend a translucent color over the background.
+fn blend(color: &crate::Color, background: Color) -> Color {
+    let alpha = color.alpha() as f32;
+    let mix = |a: u8, b: u8| (a as f32 * alpha + b as f32 * (1.0 - alpha)).round() as u8;
+    match background {
+        Color::Rgb(r, g, b) => Color::Rgb(
+            mix(color.red(), r),
+            mix(color.green(), g),
+            mix(color.blue(), b),
+        ),
+        _ => color.clone().into(),
+    }
+}
+
+/// Pixels at double the vertical resolution of the terminal. Each cell
+/// shows two pixels using the upper half block. Glyphs are drawn over the
+/// pixels.
+struct Raster {
+    area: Rect,
+    pixels: Vec<Option<Color>>,
+    glyphs: Vec<Glyph>,
+}
+
+/// A character drawn at a pixel.
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    x: u16,
+    y: u16,
+    symbol: &'static str,
+    color: Color,
+}
+
+impl Raster {
+    fn new(area: Rect) -> Self {
+        Self {
+            area,
+            pixels: vec![None; area.width as usize * area.height as usize * 2],
+            glyphs: vec![],
+        }
+    }
+
+    fn width(&self) -> u16 {
+        self.area.width
+    }
+
+    fn height(&self) -> u16 {
+        self.area.height * 2
+    }
+
+    fn set(&mut self, x: u16, y: u16, color: Color) {
+        if x < self.width() && y < self.height() {
+            self.pixels[y as usize * self.area.width as usize + x as usize] = Some(color);
+        }
+    }
+
+    fn get(&self, x: u16, y: u16) -> Option<Color> {
+        if x < self.width() && y < self.height() {
+            self.pixels[y as usize * self.area.width as usize + x as usize]
+        } else {
+            None
+        }
+    }
+
+    /// Draw `other` over this raster.
+    fn draw(&mut self, other: &Raster) {
+        let dx = other.area.x as i32 - self.area.x as i32;
+        let dy = 2 * (other.area.y as i32 - self.area.y as i32);
+        for y in 0..other.height() {
+            for x in 0..other.width() {
+                if let Some(color) = other.get(x, y) {
+                    self.set_i32(x as i32 + dx, y as i32 + dy, color);
+                }
+            }
+        }
+        for glyph in &other.glyphs {
+            self.glyph_i32(glyph.x as i32 + dx, glyph.y as i32 + dy, glyph.symbol, glyph.color);
+        }
+    }
+
+    fn set_i32(&mut self, x: i32, y: i32, color: Color) {
+        if x >= 0 && y >= 0 {
+            self.set(x as u16, y as u16, color);
+        }
+    }
+
+    fn glyph_i32(&mut self, x: i32, y: i32, symbol: &'static str, color: Color) {
+        if x >= 0 && y >= 0 && x < self.width() as i32 && y < self.height() as i32 {
+            self.glyphs.push(Glyph {
+                x: x as u16,
+                y: y as u16,
+                symbol,
+                color,
+            });
+        }
+    }
+
+    fn render(&self, buf: &mut Buffer) {
+        for row in 0..self.area.height {
+            for x in 0..self.area.width {
+                let (top, bottom) = (self.get(x, 2 * row), self.get(x, 2 * row + 1));
+                let cell = buf.get_mut(self.area.x + x, self.area.y + row);
+                match (top, bottom) {
+                    (None, None) => continue,
+                    (Some(t), Some(b)) if t == b => cell.set_symbol(" ").set_bg(t),
+                    (Some(t), b) => cell.set_symbol("▀").set_fg(t).set_bg(b.unwrap_or(Color::Reset)),
+                    (None, Some(b)) => cell.set_symbol("▄").set_fg(b).set_bg(Color::Reset),
+                };
+            }
+        }
+        for glyph in &self.glyphs {
+            let bg = self.get(glyph.x, glyph.y).unwrap_or(Color::Reset);
+            buf.get_mut(self.area.x + glyph.x, self.area.y + glyph.y / 2)
+                .set_symbol(glyph.symbol)
+                .set_fg(glyph.color)
+                .set_bg(bg);
+        }
+    }
+}
+
+/// Maps the inside of the vial to pixels.
+struct Interior {
+    size: Vec2,
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+}
+
+impl Interior {
+    /// Millimeters per pixel.
+    fn scale(&self) -> Vec2 {
+        self.size / Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    /// The center of a pixel in vial coordinates (units: mm).
+    fn to_vial(&self, x: u16, y: u16) -> Vec2 {
+        let x = x as f32 - self.x as f32 + 0.5;
+        let y = (self.y + self.height) as f32 - y as f32 - 0.5;
+        Vec2::new(x, y) * self.scale()
+    }
+
+    /// The pixel containing `pos`, clamped to the interior.
+    fn to_pixel(&self, pos: Vec2) -> (u16, u16) {
+        let p = pos / self.scale();
+        let x = p.x.clamp(0.0, (self.width - 1) as f32) as u16;
+        let y = (self.height as f32 - p.y).clamp(0.0, (self.height - 1) as f32) as u16;
+        (self.x + x, self.y + y)
+    }
+
+    fn columns(&self) -> std::ops::Range<u16> {
+        self.x..self.x + self.width
+    }
+
+    fn rows(&self) -> std::ops::Range<u16> {
+        self.y..self.y + self.height
+    }
+
+    /// The pixel row of the rim.
+    fn rim(&self) -> u16 {
+        self.y - NECK - 1
+    }
+}
+
+fn object_color(kind: ObjectKind) -> Color {
+    match kind {
+        ObjectKind::Seed => Color::Rgb(181, 137, 90),
+        ObjectKind::Creature => Color::Rgb(230, 120, 160),
+        ObjectKind::Plant => Color::Rgb(60, 160, 60),
+        ObjectKind::Shell => Color::Rgb(120, 85, 50),
+        ObjectKind::Kernel => Color::Rgb(240, 220, 170),
+    }
+}
 
-fn find_margins(size: Vec2, area: Vec2) -> Vec2 {
-    let scale = f32::min(area.x / size.x, area.y / size.y);
+fn object_symbol(kind: ObjectKind) -> &'static str {
+    match kind {
+        ObjectKind::Seed => "●",
+        ObjectKind::Creature => "&",
+        ObjectKind::Plant => "*",
+        ObjectKind::Shell => "◖",
+        ObjectKind::Kernel => "•",
+    }
+}
 
-    let new_size = scale * size;
-    (area - new_size) / 2.0
+impl<'a> VialWidget<'a> {
+    /// Draw the vial into pixels.
+    #[allow(clippy::cast_possible_truncation)]
+    fn rasterize(&self, area: Rect) -> Option<(Raster, Interior)> {
+        let VialWidget { vial, palette, .. } = self;
+        let (area, interior) = self.layout(area)?;
+        let glass = blend(&vial.glass, self.background);
+        let mut raster = Raster::new(area);
+        let (top, bottom) = (interior.rim(), interior.y + interior.height);
+        // Half the width of the inside of each pixel row below the rim. The
+        // neck is as wide as the top of the vial.
+        let center = vial.size.x / 2.0;
+        let half_widths: Vec<f32> = (top + 1..bottom)
+            .map(|y| vial.width_at(mm(interior.to_vial(0, y.max(interior.y)).y)).mm() / 2.0)
+            .collect();
+        let inside = |x: u16, y: u16| {
+            y > top
+                && y < bottom
+                && interior.columns().contains(&x)
+                && (interior.to_vial(x, y).x - center).abs() <= half_widths[(y - top - 1) as usize]
+        };
+        // Draw the glass around the inside. Only pixels beside the inside
+        // are glass, which rounds the corners.
+        for y in top + 1..=bottom {
+            for x in interior.x - 1..=interior.x + interior.width {
+                let beside = inside(x.wrapping_sub(1), y)
+                    || inside(x + 1, y)
+                    || inside(x, y - 1)
+                    || inside(x, y + 1);
+                if beside && !inside(x, y) {
+                    raster.set(x, y, glass);
+                }
+            }
+        }
+        // Draw the rim, which is a lip one pixel wider than the neck.
+        if let (Some(left), Some(right)) = (
+            interior.columns().find(|x| inside(*x, top + 1)),
+            interior.columns().rev().find(|x| inside(*x, top + 1)),
+        ) {
+            for x in left - 2..=right + 2 {
+                raster.set(x, top, glass);
+            }
+        }
+        // Draw the layers a pixel at a time so their boundaries land where
+        // their volumes say they should. Waves move the top of the top layer
+        // if it is liquid.
+        let surface = vial.surface().mm();
+        let waves = self.waves.filter(|_| vial.layers.last().is_some_and(Layer::is_liquid));
+        for y in interior.rows() {
+            let p = interior.to_vial(interior.x, y);
+            let index = match vial.layer_heights().position(|(_, _, top)| mm(p.y) < top) {
+                Some(index) => index,
+                None if waves.is_some() => vial.layers.len() - 1,
+                None => continue,
+            };
+            // Frozen layers are frosted over and boiling ones bubble. Solids
+            // are shaded and powders grainy.
+            let layer = &vial.layers[index];
+            let mut color: Color = palette.color(layer).into();
+            match layer {
+                Layer::Frozen { .. } => color = lighten(color, FROST),
+                Layer::Solid { .. } => color = darken(color, SHADE),
+                _ => {}
+            }
+            for x in interior.columns().filter(|x| inside(*x, y)) {
+                let p = interior.to_vial(x, y);
+                let offset = waves.map_or(0.0, |w| w.offset_at(p.x));
+                if index + 1 < vial.layers.len() || p.y < surface + offset {
+                    let bubble = layer.is_boiling() && (x * 3 + y * 5) % 11 == 0;
+                    let grain = matches!(layer, Layer::Powder { .. }) && (x + y) % 2 == 0;
+                    let color = if bubble {
+                        lighten(color, BUBBLE)
+                    } else if grain {
+                        darken(color, GRAIN)
+                    } else {
+                        color
+                    };
+                    raster.set(x, y, color);
+                }
+            }
+        }
+        // Draw graduation marks inside the left wall. Minor marks are left
+        // out when they would be closer than two pixels.
+        let minor = vial
+            .graduations()
+            .next()
+            .is_some_and(|(height, _)| height.mm() / interior.scale().y >= 2.0);
+        for (height, major) in vial.graduations() {
+            if !major && !minor {
+                continue;
+            }
+            let (_, y) = interior.to_pixel(Vec2::new(0.0, height.mm()));
+            let length = if major { 2 } else { 1 };
+            for x in interior.columns().filter(|x| inside(*x, y)).take(length) {
+                raster.set(x, y, glass);
+            }
+        }
+        // Seeds and shaped objects bigger than a pixel are filled in,
+        // smaller ones drawn as glyphs.
+        let scale = interior.scale();
+        for object in &vial.objects {
+            let color = object_color(object.kind);
+            let (cx, cy) = interior.to_pixel(object.pos);
+            let mut drawn = false;
+            let filled = matches!(object.kind, ObjectKind::Seed | ObjectKind::Shell | ObjectKind::Kernel);
+            if filled || !object.shape.is_circle() {
+                let r = Vec2::splat(object.extent().mm()) / scale;
+                let (rx, ry) = (r.x.ceil() as u16, r.y.ceil() as u16);
+                let rows = cy.saturating_sub(ry).max(interior.y)..(cy + ry + 1).min(interior.y + interior.height);
+                let columns = cx.saturating_sub(rx).max(interior.x)..(cx + rx + 1).min(interior.x + interior.width);
+                for y in rows {
+                    for x in columns.clone() {
+                        if object.contains(interior.to_vial(x, y)) {
+                            raster.set(x, y, color);
+                            drawn = true;
+                        }
+                    }
+                }
+            }
+            if !drawn {
+                raster.glyphs.push(Glyph {
+                    x: cx,
+                    y: cy,
+                    symbol: object_symbol(object.kind),
+                    color,
+                });
+            }
+        }
+        Some((raster, interior))
+    }
 }
 
 impl<'a> Widget for VialWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if let Some((raster, _)) = self.rasterize(area) {
+            raster.render(buf);
+        }
+    }
+}
+
+/// Fraction of the animation spent lifting the vial over the target.
+const LIFT_END: f32 = 0.3;
+/// How far the vial tilts to pour (units: radians).
+const TILT: f32 = 1.9;
+/// How high the vial is lifted on its way (units: pixels).
+const LIFT: f32 = 6.0;
+/// Fraction of the pour an object spends falling.
+const FLIGHT: f32 = 0.25;
+
+/// Animates pouring one vial into another.
+///
+/// The source vial lifts and tilts over the target, then its liquid streams
+/// down and its objects fall into the target. `t` runs from 0 to 1 like
+/// [Lerp::lerp]. Render it over an area containing both vials' areas and
+/// don't draw the two vials separately.
+#[derive(Debug, Clone)]
+pub struct PourWidget<'a> {
+    transfer: &'a Transfer,
+    from: (&'a Vial, Rect),
+    into: (&'a Vial, Rect),
+    palette: &'a Palette,
+    t: f32,
+    cell_aspect: f32,
+    background: Color,
+}
+
+fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+impl<'a> PourWidget<'a> {
+    /// Pour `from` as it was before the transfer into `into`, each with the
+    /// area it's drawn in when standing still.
+    pub fn new(
+        transfer: &'a Transfer,
+        from: (&'a Vial, Rect),
+        into: (&'a Vial, Rect),
+        palette: &'a Palette,
+        t: f32,
+    ) -> Self {
+        Self {
+            transfer,
+            from,
+            into,
+            palette,
+            t,
+            cell_aspect: 2.0,
+            background: Color::Rgb(0, 0, 0),
+        }
+    }
+
+    pub fn cell_aspect(mut self, cell_aspect: f32) -> Self {
+        self.cell_aspect = cell_aspect;
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    fn vial_widget(&self, vial: &'a Vial) -> VialWidget<'a> {
+        VialWidget::new(vial, self.palette)
+            .cell_aspect(self.cell_aspect)
+            .background(self.background)
+    }
+}
+
+impl<'a> Widget for PourWidget<'a> {
     #[allow(clippy::cast_possible_truncation)]
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let VialWidget(vial, palette) = self;
-        // XXX: Render it with the correct aspect ratio.
-        let margins = find_margins(
-            Vec2::new(vial.size.x, vial.size.y / 2.0),
-            Vec2::new(area.width as f32, area.height as f32),
+        let (from, from_area) = self.from;
+        let (into, into_area) = self.into;
+        let lift = smoothstep(self.t / LIFT_END);
+        let pour_t = ((self.t - LIFT_END) / (1.0 - LIFT_END)).clamp(0.0, 1.0);
+        let Some((from_t, into_t)) = self.transfer.lerp(from, into, pour_t) else {
+            return;
+        };
+        // Objects that move, in the order they move.
+        let (_, result) = self.transfer.result(from, into);
+        let moving: Vec<&Object> = result
+            .objects
+            .iter()
+            .filter(|o| !into.objects.iter().any(|p| p.id == o.id))
+            .collect();
+        // Objects still falling are in neither vial.
+        let count = moving.len() as f32;
+        let falling: Vec<(&Object, f32)> = moving
+            .iter()
+            .enumerate()
+            .filter_map(|(k, o)| {
+                let arrive = (k + 1) as f32 / count;
+                let f = 1.0 - (arrive - pour_t) / FLIGHT;
+                (pour_t > 0.0 && (0.0..1.0).contains(&f)).then_some((*o, f))
+            })
+            .collect();
+        let mut from_t = from_t;
+        let mut into_t = into_t;
+        from_t.objects.retain(|o| !falling.iter().any(|(p, _)| p.id == o.id));
+        into_t.objects.retain(|o| !falling.iter().any(|(p, _)| p.id == o.id));
+
+        let mut raster = Raster::new(area);
+        let Some((target, target_interior)) = self.vial_widget(&into_t).rasterize(into_area) else {
+            return;
+        };
+        let Some((source, source_interior)) = self.vial_widget(&from_t).rasterize(from_area) else {
+            return;
+        };
+        raster.draw(&target);
+
+        // Pixel coordinates relative to `area`.
+        let offset = |r: &Raster| {
+            Vec2::new(
+                r.area.x as f32 - area.x as f32,
+                2.0 * (r.area.y as f32 - area.y as f32),
+            )
+        };
+        let to_right = into_area.x > from_area.x;
+        // Tilt about the corner of the rim facing the target.
+        let lip = Vec2::new(
+            if to_right {
+                (source_interior.x + source_interior.width + 1) as f32 + 0.5
+            } else {
+                source_interior.x as f32 - 1.5
+            },
+            source_interior.rim() as f32 + 0.5,
         );
-        // dbg!(margins);
-        let [area] = Layout::vertical([Constraint::Percentage(100)])
-            .horizontal_margin(margins.x as u16)
-            .vertical_margin(margins.y as u16)
-            .areas(area);
-        // dbg!(area);
-        let border = Style::new().bg(vial.glass.clone().into());
-        // let view_volume = (area.width - 2) * (area.height - 1);
-        let volume_per_row = vial.max_volume / (area.height - 1) as f32;
-
-        if area.height > 1 {
-            // Draw bottom.
-            buf.set_string(
-                area.x + 1,
-                area.y + area.height - 1,
-                " ".repeat((area.width - 2) as usize),
-                border,
+        let rest = lip + offset(&source);
+        let over = offset(&target)
+            + Vec2::new(
+                target_interior.x as f32 + target_interior.width as f32 / 2.0,
+                target_interior.rim() as f32 - 1.5,
             );
-        }
-        for j in area.y..area.y + area.height {
-            // Draw vertical sides.
-            buf.set_string(area.x, j, " ", border);
-            buf.set_string(area.x + area.width - 1, j, " ", border);
-        }
-        // Draw contents.
-        let mut j = area.y + area.height - 2;
-        // let mut volume_remaining = vial.volume;
-        let mut slop = 0.0;
-        // dbg!(area);
-        for layer in &vial.layers {
-            match layer {
-                Layer::Liquid { id, mut volume } => {
-                    let style = Style::new().bg(palette[*id].clone().into());
-                    volume += slop;
-
-                    while volume > 0.0 {
-                        buf.set_string(area.x + 1, j, " ".repeat((area.width - 2) as usize), style);
-                        volume -= volume_per_row;
-                        j = j.saturating_sub(1);
+        let pivot = rest.lerp(over, lift) - Vec2::new(0.0, LIFT * (lift * std::f32::consts::PI).sin());
+        let angle = if to_right { TILT } else { -TILT } * lift;
+        // Pixels are taller than they are wide unless cells are twice as
+        // high as they are wide.
+        let k = self.cell_aspect / 2.0;
+        let (sin, cos) = angle.sin_cos();
+        let rotate = |v: Vec2, sin: f32| {
+            let v = Vec2::new(v.x, v.y * k);
+            let v = Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+            Vec2::new(v.x, v.y / k)
+        };
+        // Map each pixel back into the source to find its color.
+        for y in 0..raster.height() {
+            for x in 0..raster.width() {
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let s = lip + rotate(p - pivot, -sin);
+                if s.x >= 0.0 && s.y >= 0.0 {
+                    if let Some(color) = source.get(s.x as u16, s.y as u16) {
+                        raster.set(x, y, color);
                     }
-                    slop = volume;
-                    // dbg!(volume);
-                    // dbg!(slop);
                 }
             }
         }
-        for object in &vial.objects {
-            match object.kind {
-                ObjectKind::Seed => {
-                    let size = object.size as u16;
-                    let x = (object.pos.x / self.0.size.x * (area.width - 3) as f32) as i16;
-                    let y = (object.pos.y / self.0.size.y * (area.height - 2) as f32) as i16;
-                    // dbg!((x, y));
-                    for j in 0..(size / 2).max(1) {
-                        buf.set_string(
-                            ((area.x + (size / 2).max(1)) as i16 + x) as u16,
-                            ((area.y + area.height - 2 - j) as i16 - y) as u16,
-                            " ".repeat(size as usize),
-                            border,
-                        );
-                    }
+        for glyph in &source.glyphs {
+            let g = Vec2::new(glyph.x as f32 + 0.5, glyph.y as f32 + 0.5);
+            let p = pivot + rotate(g - lip, sin);
+            raster.glyph_i32(p.x.floor() as i32, p.y.floor() as i32, glyph.symbol, glyph.color);
+        }
+
+        // Stream the liquid from the lip to the surface of the target.
+        let surface = target_interior.to_pixel(Vec2::new(0.0, into_t.surface().mm())).1 as f32
+            + offset(&target).y;
+        if lift >= 1.0 && pour_t < 1.0 && matches!(self.transfer, Transfer::Liquid | Transfer::LiquidAmount(_)) {
+            if let Some(layer) = from.layers.last() {
+                let color: Color = self.palette.color(layer).into();
+                for y in pivot.y.ceil() as i32..surface as i32 {
+                    raster.set_i32(pivot.x as i32, y, color);
+                }
+            }
+        }
+        // Objects fall from the lip to where they land in the target.
+        for (obj, f) in falling {
+            let x = target_interior.to_pixel(Vec2::new(obj.pos.x, 0.0)).0;
+            let land = Vec2::new(x as f32 + offset(&target).x, surface);
+            let x = pivot.x + (land.x - pivot.x) * f;
+            let y = pivot.y + (land.y - pivot.y) * f * f;
+            raster.glyph_i32(x as i32, y as i32, object_symbol(obj.kind), object_color(obj.kind));
+        }
+        raster.render(buf);
+    }
+}
+
+/// A scrollable list of levels with a preview of each.
+#[derive(Debug, Clone)]
+pub struct LevelSelect<'a> {
+    pub levels: &'a [Level],
+    pub profile: &'a Profile,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LevelSelectState {
+    pub selected: usize,
+    pub offset: usize,
+}
+
+impl LevelSelectState {
+    pub fn next(&mut self, count: usize) {
+        self.selected = (self.selected + 1).min(count.saturating_sub(1));
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+const ENTRY_HEIGHT: u16 = 7;
+const PREVIEW_WIDTH: u16 = 6;
+
+impl<'a> StatefulWidget for LevelSelect<'a> {
+    type State = LevelSelectState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let visible = (area.height / ENTRY_HEIGHT).max(1) as usize;
+        // Scroll so the selected entry is visible.
+        if state.selected < state.offset {
+            state.offset = state.selected;
+        } else if state.selected >= state.offset + visible {
+            state.offset = state.selected + 1 - visible;
+        }
+        for (row, index) in (state.offset..self.levels.len()).take(visible).enumerate() {
+            let level = &self.levels[index];
+            let entry = Rect::new(area.x, area.y + row as u16 * ENTRY_HEIGHT, area.width, ENTRY_HEIGHT)
+                .intersection(area);
+            let unlocked = self.profile.is_unlocked(index);
+            let block = Block::default().borders(Borders::ALL).border_style(
+                if index == state.selected {
+                    Style::new().yellow()
+                } else {
+                    Style::new().dark_gray()
+                },
+            );
+            let inner = block.inner(entry);
+            block.render(entry, buf);
+
+            let preview_width = (PREVIEW_WIDTH * level.potions.len() as u16).min(inner.width / 2);
+            let [text, preview] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Length(preview_width)])
+                    .areas(inner);
+            let best = match self.profile.best(index) {
+                Some(score) => score.to_string(),
+                None if unlocked => "Not completed".into(),
+                None => "Locked".into(),
+            };
+            let style = if unlocked { Style::new() } else { Style::new().dark_gray() };
+            Paragraph::new(vec![
+                Line::from(format!("{}. {}", index + 1, level.title(index))).bold(),
+                Line::from(format!("Goal: {}", level.goal)),
+                Line::from(best),
+            ])
+            .style(style)
+            .render(text, buf);
+
+            if preview.width >= PREVIEW_WIDTH && preview.height >= 3 {
+                let vials = Layout::horizontal(
+                    std::iter::repeat_n(Constraint::Length(PREVIEW_WIDTH), level.potions.len()),
+                )
+                .split(preview);
+                for (vial, rect) in level.potions.iter().zip(vials.iter()) {
+                    VialWidget::new(vial, &level.palette).render(*rect, buf);
                 }
-                _ => todo!(),
             }
         }
     }
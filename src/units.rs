@@ -0,0 +1,230 @@
+//! Typed lengths and volumes.
+//!
+//! Lengths are usually in mm and volumes in ml. Positions and sizes are
+//! `Vec2` in mm because `quantities` has no vector type.
+use quantities::prelude::*;
+pub use quantities::area::{Area, SQUARE_METER};
+pub use quantities::length::{Length, METER, MILLIMETER};
+pub use quantities::volume::{Volume, CUBIC_METER, CUBIC_MILLIMETER, MILLILITER};
+pub use quantities::AmountT;
+
+pub fn mm(amount: f32) -> Length {
+    Length::new(amount as AmountT, MILLIMETER)
+}
+
+pub fn m(amount: f32) -> Length {
+    Length::new(amount as AmountT, METER)
+}
+
+pub fn m2(amount: f32) -> Area {
+    Area::new(amount as AmountT, SQUARE_METER)
+}
+
+pub fn ml(amount: f32) -> Volume {
+    Volume::new(amount as AmountT, MILLILITER)
+}
+
+pub fn mm3(amount: f32) -> Volume {
+    Volume::new(amount as AmountT, CUBIC_MILLIMETER)
+}
+
+/// The smaller of two quantities. Quantities are only partially ordered.
+pub fn min<Q: PartialOrd>(a: Q, b: Q) -> Q {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+/// The larger of two quantities.
+pub fn max<Q: PartialOrd>(a: Q, b: Q) -> Q {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+/// The distance between two quantities.
+pub fn abs_diff<Q: PartialOrd + std::ops::Sub<Output = Q>>(a: Q, b: Q) -> Q {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// Read a length as a plain number for geometry and physics.
+pub trait LengthExt {
+    fn mm(&self) -> f32;
+    fn m(&self) -> f32;
+}
+
+impl LengthExt for Length {
+    fn mm(&self) -> f32 {
+        self.equiv_amount(MILLIMETER) as f32
+    }
+
+    fn m(&self) -> f32 {
+        self.equiv_amount(METER) as f32
+    }
+}
+
+/// Read an area as a plain number.
+pub trait AreaExt {
+    fn m2(&self) -> f32;
+}
+
+impl AreaExt for Area {
+    fn m2(&self) -> f32 {
+        self.equiv_amount(SQUARE_METER) as f32
+    }
+}
+
+/// Read a volume as a plain number.
+pub trait VolumeExt {
+    fn ml(&self) -> f32;
+    fn mm3(&self) -> f32;
+    fn m3(&self) -> f32;
+}
+
+impl VolumeExt for Volume {
+    fn ml(&self) -> f32 {
+        self.equiv_amount(MILLILITER) as f32
+    }
+
+    fn mm3(&self) -> f32 {
+        self.equiv_amount(CUBIC_MILLIMETER) as f32
+    }
+
+    fn m3(&self) -> f32 {
+        self.equiv_amount(CUBIC_METER) as f32
+    }
+}
+
+/// Serde helpers that write quantities with their unit, like `"50 ml"`, and
+/// read either that or a plain number in the default unit.
+pub mod serde {
+    use super::*;
+    use ::serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Amount(AmountT),
+        Text(String),
+    }
+
+    fn write<Q: Quantity, S: Serializer>(q: &Q, serializer: S) -> Result<S::Ok, S::Error> {
+        // Go through f32 so values that started as f32 do not gain digits.
+        serializer.collect_str(&format_args!("{} {}", q.amount() as f32, q.unit().symbol()))
+    }
+
+    fn read<'de, Q: Quantity, D: Deserializer<'de>>(
+        deserializer: D,
+        unit: Q::UnitType,
+    ) -> Result<Q, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Amount(amount) => Ok(Q::new(amount, unit)),
+            Repr::Text(text) => {
+                let (amount, symbol) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+                let amount: AmountT = amount.parse().map_err(D::Error::custom)?;
+                let unit = match symbol.trim() {
+                    "" => unit,
+                    symbol => Q::unit_from_symbol(symbol)
+                        .ok_or_else(|| D::Error::custom(format!("unknown unit {:?}", symbol)))?,
+                };
+                Ok(Q::new(amount, unit))
+            }
+        }
+    }
+
+    /// A length in mm by default.
+    pub mod length {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(q: &Length, serializer: S) -> Result<S::Ok, S::Error> {
+            write(q, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Length, D::Error> {
+            read(deserializer, MILLIMETER)
+        }
+    }
+
+    /// A volume in ml by default.
+    pub mod volume {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(q: &Volume, serializer: S) -> Result<S::Ok, S::Error> {
+            write(q, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Volume, D::Error> {
+            read(deserializer, MILLILITER)
+        }
+    }
+
+    /// An optional volume in ml by default.
+    pub mod option_volume {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(q: &Option<Volume>, serializer: S) -> Result<S::Ok, S::Error> {
+            match q {
+                Some(q) => serializer.serialize_some(&Wrap(*q)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Volume>, D::Error> {
+            Ok(Option::<Wrap>::deserialize(deserializer)?.map(|w| w.0))
+        }
+
+        #[derive(::serde::Deserialize, ::serde::Serialize)]
+        #[serde(transparent)]
+        struct Wrap(#[serde(with = "super::volume")] Volume);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Sample {
+        #[serde(with = "serde::volume")]
+        volume: Volume,
+        #[serde(with = "serde::length")]
+        length: Length,
+        #[serde(default, with = "serde::option_volume")]
+        step: Option<Volume>,
+    }
+
+    #[test]
+    fn test_serde() {
+        let sample = Sample {
+            volume: ml(50.0),
+            length: mm(2.5),
+            step: Some(ml(10.0)),
+        };
+        let s = ron::to_string(&sample).unwrap();
+        assert_eq!(s, r#"(volume:"50 ml",length:"2.5 mm",step:Some("10 ml"))"#);
+        assert_eq!(ron::from_str::<Sample>(&s).unwrap(), sample);
+        // Plain numbers use the default unit and other units convert.
+        let sample: Sample = ron::from_str(r#"(volume: "0.05 l", length: 2.5)"#).unwrap();
+        assert_eq!(sample.volume.ml(), 50.0);
+        assert_eq!(sample.length, mm(2.5));
+        assert_eq!(sample.step, None);
+        assert!(ron::from_str::<Sample>(r#"(volume: "5 parsecs", length: 1)"#).is_err());
+    }
+
+    #[test]
+    fn test_mm3() {
+        assert!((ml(1.0).mm3() - 1_000.0).abs() < 1e-3);
+        assert!((mm3(2_500.0).ml() - 2.5).abs() < 1e-6);
+    }
+}
@@ -3,29 +3,41 @@ bitflags::bitflags! {
     pub struct UserDataFlags: u8 {
         const OBJECT = 0b00000001;
         const WALL = 0b00000010;
+        const PARTICLE = 0b00000100;
     }
 }
 
+/// What a collider is, packed into rapier's user data as the flags above an
+/// id that uses the low 64 bits.
 pub struct UserData {
     pub flags: UserDataFlags,
-    pub id: u8,
+    pub id: u64,
 }
 
 impl UserData {
     pub fn wall(id: u8) -> Self {
         Self {
             flags: UserDataFlags::WALL,
-            id
+            id: id.into()
         }
     }
 
-    pub fn object(id: u8) -> Self {
+    /// The object with [Object::id](crate::Object::id) `id`.
+    pub fn object(id: u64) -> Self {
         Self {
             flags: UserDataFlags::OBJECT,
             id
         }
     }
 
+    /// A particle of the liquid with palette index `id`.
+    pub fn particle(id: u64) -> Self {
+        Self {
+            flags: UserDataFlags::PARTICLE,
+            id
+        }
+    }
+
     pub fn is_wall(&self) -> bool {
         self.flags.contains(UserDataFlags::WALL)
     }
@@ -33,49 +45,38 @@ impl UserData {
     pub fn is_object(&self) -> bool {
         self.flags.contains(UserDataFlags::OBJECT)
     }
-}
-
-impl From<UserData> for u64 {
-    fn from(user_data: UserData) -> u64 {
-        (user_data.flags.bits() as u64) << 8 | user_data.id as u64
-    }
-}
-
-impl From<u64> for UserData {
-    fn from(n: u64) -> UserData {
-        UserData {
-            id: (n & 0xff) as u8,
-            flags: UserDataFlags::from_bits((n >> 8 & 0xff) as u8).expect("flags")
-        }
-    }
-}
-
-impl From<UserData> for i64 {
-    fn from(user_data: UserData) -> i64 {
-        (user_data.flags.bits() as i64) << 8 | user_data.id as i64
-    }
-}
 
-impl From<i64> for UserData {
-    fn from(n: i64) -> UserData {
-        UserData {
-            id: (n & 0xff) as u8,
-            flags: UserDataFlags::from_bits((n >> 8 & 0xff) as u8).expect("flags")
-        }
+    pub fn is_particle(&self) -> bool {
+        self.flags.contains(UserDataFlags::PARTICLE)
     }
 }
 
 impl From<UserData> for u128 {
     fn from(user_data: UserData) -> u128 {
-        (user_data.flags.bits() as u128) << 8 | user_data.id as u128
+        (user_data.flags.bits() as u128) << 64 | user_data.id as u128
     }
 }
 
 impl From<u128> for UserData {
     fn from(n: u128) -> UserData {
         UserData {
-            id: (n & 0xff) as u8,
-            flags: UserDataFlags::from_bits(((n >> 8) & 0xff) as u8).expect("flags")
+            id: n as u64,
+            flags: UserDataFlags::from_bits((n >> 64) as u8).expect("flags")
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let n: u128 = UserData::object(u64::MAX).into();
+        let user_data = UserData::from(n);
+        assert!(user_data.is_object() && !user_data.is_wall());
+        assert_eq!(user_data.id, u64::MAX);
+        let n: u128 = UserData::wall(3).into();
+        assert!(UserData::from(n).is_wall());
+    }
+}
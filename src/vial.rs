@@ -1,12 +1,15 @@
 use approx::abs_diff_eq;
 use derived_deref::{Deref, DerefMut};
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashSet};
 use crate::Palette;
 use bevy_color::{Mix, Srgba};
 use bevy_math::Vec2;
 use serde::{Deserialize, Serialize};
-use super::{Object, ObjectFlags, ByHeight};
-use crate::user_data::{UserData};
+use super::{Effect, Object, ObjectFlags, ByHeight, Liquid, Material, Outcome, Powder, Rule, Shape};
+use crate::rule;
+use crate::constant::ROOM_TEMPERATURE;
+use crate::units::{self, mm, ml, Length, LengthExt, Volume, VolumeExt};
+use std::f32::consts::PI;
 
 #[derive(Debug, Clone, Deref, DerefMut, Deserialize, Serialize)]
 pub struct Color(color_art::Color);
@@ -51,6 +54,7 @@ impl From<bevy_color::Srgba> for Color {
 ///
 /// Here's what the coordinate space looks like.
 ///
+/// ```text
 ///              ^
 ///              |
 /// y in [0, h]  +-------------+
@@ -71,57 +75,228 @@ impl From<bevy_color::Srgba> for Color {
 ///              +-------------+->   x in [0, w]
 ///
 ///                    Vial
+/// ```
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Vial {
     pub layers: Vec<Layer>,
     pub objects: Vec<Object>,
-    pub max_volume: f32,
+    #[serde(with = "units::serde::volume")]
+    pub max_volume: Volume,
     pub glass: Color,
-    /// units: mm
+    /// Width and height of the inside (units: mm). See [Vial::width] and
+    /// [Vial::height] for typed lengths.
     pub size: Vec2,
+    /// Volume between graduation marks on the glass.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "units::serde::option_volume")]
+    pub graduation: Option<Volume>,
+    #[serde(default, skip_serializing_if = "Shape::is_straight")]
+    pub shape: Shape,
+    /// Derive heights from a round cross-section so volumes are real ml.
+    /// Otherwise `max_volume` fills the vial to the top whatever its size.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub physical: bool,
+    /// What the walls and floor are made of.
+    #[serde(default, skip_serializing_if = "Material::is_glass")]
+    pub material: Material,
 }
 
 impl Default for Vial {
     fn default() -> Self {
         Self {
             layers: vec![],
-            max_volume: 100.0,
+            max_volume: ml(100.0),
             objects: vec![],
             size: Vec2::new(25.0, 75.0),
             glass: color_art::Color::from_rgba(255, 255, 255, 0.5)
                 .unwrap()
                 .into(),
+            graduation: None,
+            shape: Shape::Straight,
+            physical: false,
+            material: Material::GLASS,
         }
     }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Layer {
-    Liquid { id: usize, volume: f32 },
+    Liquid {
+        id: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(default, skip_serializing_if = "Liquid::is_water")]
+        liquid: Liquid,
+        /// Temperature (units: °C).
+        #[serde(default = "room_temperature", skip_serializing_if = "is_room_temperature")]
+        temperature: f32,
+    },
+    /// A liquid frozen solid. It can't be poured until it thaws.
+    Frozen {
+        id: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(default, skip_serializing_if = "Liquid::is_water")]
+        liquid: Liquid,
+        /// Temperature (units: °C).
+        #[serde(default = "room_temperature", skip_serializing_if = "is_room_temperature")]
+        temperature: f32,
+    },
+    /// A powder such as sand or salt. It sinks below liquids and objects
+    /// rest on it.
+    Powder {
+        id: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(default)]
+        powder: Powder,
+        /// Temperature (units: °C).
+        #[serde(default = "room_temperature", skip_serializing_if = "is_room_temperature")]
+        temperature: f32,
+    },
+    /// A solid such as wax or stone. It can't be poured and objects rest
+    /// on it.
+    Solid {
+        id: usize,
+        #[serde(with = "units::serde::volume")]
+        volume: Volume,
+        #[serde(default)]
+        material: Material,
+        /// Temperature (units: °C).
+        #[serde(default = "room_temperature", skip_serializing_if = "is_room_temperature")]
+        temperature: f32,
+    },
     // Empty,
 }
 
-// #[derive(Debug, Clone)]
-// pub struct LiquidProp {
-//     pub density: f32,
-// }
-//
-
+pub(crate) fn room_temperature() -> f32 {
+    ROOM_TEMPERATURE
+}
 
-// pub enum Liquid {
-//     Water,
-//     Oil,
-// }
+pub(crate) fn is_room_temperature(temperature: &f32) -> bool {
+    *temperature == ROOM_TEMPERATURE
+}
 
 impl Layer {
-    pub fn volume(&self) -> f32 {
+    /// A layer of `liquid` at room temperature.
+    pub fn new(id: usize, volume: Volume, liquid: Liquid) -> Self {
+        Layer::Liquid { id, volume, liquid, temperature: ROOM_TEMPERATURE }
+    }
+
+    /// A layer of water at room temperature.
+    pub fn water(id: usize, volume: Volume) -> Self {
+        Layer::new(id, volume, Liquid::WATER)
+    }
+
+    pub fn volume(&self) -> Volume {
         match self {
-            Layer::Liquid { volume, .. } => *volume,
+            Layer::Liquid { volume, .. }
+            | Layer::Frozen { volume, .. }
+            | Layer::Powder { volume, .. }
+            | Layer::Solid { volume, .. } => *volume,
             // Layer::Empty => 0.0,
         }
     }
+
+    fn volume_mut(&mut self) -> &mut Volume {
+        match self {
+            Layer::Liquid { volume, .. }
+            | Layer::Frozen { volume, .. }
+            | Layer::Powder { volume, .. }
+            | Layer::Solid { volume, .. } => volume,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        match self {
+            Layer::Liquid { id, .. } | Layer::Frozen { id, .. } | Layer::Powder { id, .. } | Layer::Solid { id, .. } => *id,
+        }
+    }
+
+    /// The liquid in the layer, frozen or not.
+    pub fn liquid(&self) -> Option<&Liquid> {
+        match self {
+            Layer::Liquid { liquid, .. } | Layer::Frozen { liquid, .. } => Some(liquid),
+            Layer::Powder { .. } | Layer::Solid { .. } => None,
+        }
+    }
+
+    /// Temperature (units: °C).
+    pub fn temperature(&self) -> f32 {
+        match self {
+            Layer::Liquid { temperature, .. }
+            | Layer::Frozen { temperature, .. }
+            | Layer::Powder { temperature, .. }
+            | Layer::Solid { temperature, .. } => *temperature,
+        }
+    }
+
+    /// Whether the layer flows: objects float in it and it mixes.
+    pub fn is_liquid(&self) -> bool {
+        matches!(self, Layer::Liquid { .. })
+    }
+
+    /// Whether the layer stays put when the vial is poured.
+    pub fn is_solid(&self) -> bool {
+        matches!(self, Layer::Frozen { .. } | Layer::Solid { .. })
+    }
+
+    /// Whether the layer is a liquid at its boiling point.
+    pub fn is_boiling(&self) -> bool {
+        matches!(self, Layer::Liquid { liquid, temperature, .. } if *temperature >= liquid.boils)
+    }
+
+    /// Heat the layer by `change` (units: °C), or cool it if negative,
+    /// freezing or thawing it as it crosses its freezing point. Heat past
+    /// boiling is held until [Layer::boil] spends it.
+    fn heat(&mut self, change: f32) {
+        let (id, volume, temperature) = (self.id(), self.volume(), self.temperature() + change);
+        let liquid = match self {
+            Layer::Liquid { liquid, .. } | Layer::Frozen { liquid, .. } => *liquid,
+            Layer::Powder { temperature: t, .. } | Layer::Solid { temperature: t, .. } => {
+                *t = temperature;
+                return;
+            }
+        };
+        *self = if temperature <= liquid.freezes {
+            Layer::Frozen { id, volume, liquid, temperature }
+        } else {
+            Layer::Liquid { id, volume, liquid, temperature }
+        };
+    }
+
+    /// Boil the layer for `dt` (units: s), spending heat past its boiling
+    /// point at [BOIL_RATE] to boil away [BOIL_OFF] per °C. Return whether
+    /// any liquid boiled away.
+    fn boil(&mut self, dt: f32) -> bool {
+        let Layer::Liquid { volume, liquid, temperature, .. } = self else {
+            return false;
+        };
+        let excess = *temperature - liquid.boils;
+        if excess <= 0.0 {
+            return false;
+        }
+        let spent = excess.min(BOIL_RATE * dt);
+        *volume = units::max(*volume - ml(BOIL_OFF * spent), ml(0.0));
+        *temperature -= spent;
+        true
+    }
 }
 
+/// Rise in temperature that doubles how fast objects react (units: °C).
+pub const REACTION_DOUBLING: f32 = 10.0;
+/// Volume of liquid a dye colors for each mm² of it that dissolves (units:
+/// ml).
+pub const DYE_STRENGTH: f32 = 1.0;
+/// Time for an object to take on most of the temperature of the liquid
+/// around it (units: s).
+const WARM_TIME: f32 = 2.0;
+
+/// Volume of liquid boiled away by each °C of heat past its boiling point
+/// (units: ml).
+pub const BOIL_OFF: f32 = 0.5;
+/// How fast heat past a liquid's boiling point boils it away (units: °C/s).
+pub const BOIL_RATE: f32 = 10.0;
+
 #[derive(Debug, Clone)]
 pub enum Transition {
     MoveDown(Vial),
@@ -130,7 +305,10 @@ pub enum Transition {
 
 #[derive(Debug, Clone)]
 pub enum Transfer {
+    /// Pour the top layer, or as much of it as fits.
     Liquid,
+    /// Pour at most this volume of the top layer.
+    LiquidAmount(Volume),
     Object,
 }
 
@@ -151,7 +329,7 @@ impl Lerp<Vial> for Transfer {
         let mut a = a.clone();
         let mut b = b.clone();
         match self {
-            Transfer::Liquid => {
+            Transfer::Liquid | Transfer::LiquidAmount(_) => {
                 let mut objects_top_a: Vec<usize> = if !a.layers.is_empty() {
                     let top_layer_a = a.layers.len() - 1;
                     a.objects
@@ -168,44 +346,54 @@ impl Lerp<Vial> for Transfer {
                     // let r = 0..a.objects.len();
                     // r.collect()
                 };
-                let Layer::Liquid {
-                    volume: ref mut volume_a,
-                    id: id_a,
-                } = a.layers.last_mut().unwrap();
-                let total_volume_b = b.vol();
-                if b.layers.is_empty() {
-                    b.layers.push(Layer::Liquid {
-                        volume: 0.0,
-                        id: *id_a,
-                    });
+                let top_a = a.layers.last().cloned()?;
+                if !matches!(top_a, Layer::Liquid { .. } | Layer::Powder { .. }) {
+                    return None;
                 }
-                if let Some(Layer::Liquid {
-                    volume: ref mut volume_b,
-                    id: id_b,
-                }) = b.layers.last_mut()
-                {
-                    if id_a != id_b {
-                        return None;
+                match b.layers.last() {
+                    Some(top_b) if top_b.is_liquid() && top_a.is_liquid() && top_b.id() != top_a.id() => return None,
+                    // Liquid lands on a powder or solid as it would on the glass.
+                    Some(top_b) if top_b.is_liquid() => {}
+                    _ => {
+                        if let Layer::Liquid { id, liquid, temperature, .. } = top_a {
+                            b.layers.push(Layer::Liquid { volume: ml(0.0), id, liquid, temperature });
+                        }
                     }
-                    // assert_eq!(id_a, id_b);
-                    let empty_volume_b = b.max_volume - total_volume_b;
-                    assert!(empty_volume_b > 0.0);
-                    // let t = 1.0;
-                    if *volume_a > empty_volume_b * t {
-                        // We pour some.
-                        *volume_a -= empty_volume_b * t;
-                        a.discard_empties();
-                        *volume_b += empty_volume_b * t;
-                        b.discard_empties();
-                    } else {
-                        // We pour all.
-                        *volume_b += *volume_a * t;
-                        *volume_a = 0.0;
+                }
+                let empty_volume_b = b.max_volume - b.vol();
+                if empty_volume_b <= ml(0.0) {
+                    return None;
+                }
+                let mut amount = units::min(top_a.volume(), empty_volume_b);
+                if let Transfer::LiquidAmount(limit) = self {
+                    amount = units::min(amount, *limit);
+                    // Objects only follow a measured pour that empties the layer.
+                    if amount < top_a.volume() {
+                        objects_top_a.clear();
                     }
                 }
-                if matches!(a.layers.last().unwrap(), Layer::Liquid { volume: 0.0, .. }) {
-                    a.layers.pop();
+                let poured = amount * t as f64;
+                if let Some(top) = a.layers.last_mut() {
+                    *top.volume_mut() = top_a.volume() - poured;
+                }
+                match top_a {
+                    Layer::Powder { id, powder, temperature, .. } => {
+                        if poured > ml(0.0) {
+                            b.settle(Layer::Powder { id, volume: poured, powder, temperature });
+                        }
+                    }
+                    _ => {
+                        if let Some(Layer::Liquid { volume: volume_b, temperature: temperature_b, .. }) = b.layers.last_mut() {
+                            // What is poured and what it lands in even out.
+                            let total = (*volume_b + poured).ml();
+                            if total > 0.0 {
+                                *temperature_b = (*temperature_b * volume_b.ml() + top_a.temperature() * poured.ml()) / total;
+                            }
+                            *volume_b = *volume_b + poured;
+                        }
+                    }
                 }
+                a.discard_empties();
                 // We also have to transfer objects that are in the liquid.
                 let transfer_count = (objects_top_a.len() as f32 * t).ceil() as usize;
 
@@ -217,7 +405,7 @@ impl Lerp<Vial> for Transfer {
                     // XXX: This is causing a panic.
                     obj.pos.y = b.size.y;
                     // obj.pos.y = b.size.y * 0.8;
-                    b.objects.push(obj);
+                    b.receive(obj);
                 }
             }
             Transfer::Object => {
@@ -247,10 +435,10 @@ impl Lerp<Vial> for Transfer {
                     // obj.pos.y = b.size.y * 0.8;
                     //
                     obj.flags |= ObjectFlags::ENTER_VIAL;
-                    if b.layers.is_empty() && obj.size > 1.0 {
+                    if b.layers.is_empty() && obj.can_break() {
                         obj.flags |= ObjectFlags::EXPECT_BREAK;
                     }
-                    b.objects.push(obj);
+                    b.receive(obj);
                 }
 
                 // let Layer::Object { obj: o, .. } = a.layers.last_mut().unwrap() else { panic!(); };
@@ -270,8 +458,8 @@ impl Lerp<Vial> for Transfer {
 }
 
 pub enum VialLoc {
-    Layer { index: usize, height: f32 },
-    Top { height: f32 },
+    Layer { index: usize, height: Length },
+    Top { height: Length },
 }
 
 impl Vial {
@@ -286,65 +474,228 @@ impl Vial {
 
     // }
 
-    pub fn in_layer(&self, point: Vec2, r: f32) -> Option<VialLoc> {
-        let height_per_vol = self.size.y / self.max_volume;
+    /// How many layers from the bottom lie under the topmost layer that
+    /// doesn't flow, and so under all the liquid that can move.
+    pub(crate) fn settled(&self) -> usize {
+        self.layers.iter().rposition(|l| !l.is_liquid()).map_or(0, |i| i + 1)
+    }
+
+    /// An id that no object in the vial has: one past the largest.
+    pub fn next_object_id(&self) -> u64 {
+        self.objects.iter().map(|o| o.id + 1).max().unwrap_or(0)
+    }
 
-        let mut height = 0.0;
+    /// Put an object poured in with the others, giving it a new id if one
+    /// of them already has its id.
+    fn receive(&mut self, mut obj: Object) {
+        if self.objects.iter().any(|o| o.id == obj.id) {
+            obj.id = self.next_object_id();
+        }
+        self.objects.push(obj);
+    }
+
+    /// Let powder sink through the liquid on top and land on what is below.
+    /// It joins a layer of the same powder that it lands on.
+    fn settle(&mut self, layer: Layer) {
+        let index = self.settled();
+        match (self.layers[..index].last_mut(), layer) {
+            (Some(Layer::Powder { id, volume, temperature, .. }), Layer::Powder { id: new_id, volume: added, temperature: added_temperature, .. })
+                if *id == new_id =>
+            {
+                let total = (*volume + added).ml();
+                *temperature = (*temperature * volume.ml() + added_temperature * added.ml()) / total;
+                *volume = *volume + added;
+            }
+            (_, layer) => self.layers.insert(index, layer),
+        }
+    }
+
+    /// Which layer a circle of radius `r` at `point` (units: mm) reaches.
+    /// Layers that don't flow are floors: a circle resting on one is in
+    /// the liquid above it, or at the top if there is none.
+    pub fn in_layer(&self, point: Vec2, r: Length) -> Option<VialLoc> {
+        let y = mm(point.y) - r;
+        let mut volume = ml(0.0);
         for (i, layer) in self.layers.iter().enumerate() {
-            height += height_per_vol * layer.volume();
-            if point.y < height + r {
+            volume = volume + layer.volume();
+            let height = self.height_of(volume);
+            if y < height && layer.is_liquid() {
                 return Some(VialLoc::Layer { index: i, height });
             }
         }
-        if point.y < self.size.y + r {
+        if y < self.height() {
             return Some(VialLoc::Top {
-                height: self.size.y,
+                height: self.height(),
             });
         }
         None
     }
 
-    pub fn vol(&self) -> f32 {
-        self.layers.iter().map(|l: &Layer| l.volume()).sum()
+    /// Each layer with the heights of its bottom and top.
+    pub fn layer_heights(&self) -> impl Iterator<Item = (&Layer, Length, Length)> + '_ {
+        let mut volume = ml(0.0);
+        self.layers.iter().map(move |layer| {
+            let bottom = self.height_of(volume);
+            volume = volume + layer.volume();
+            (layer, bottom, self.height_of(volume))
+        })
+    }
+
+    pub fn width(&self) -> Length {
+        mm(self.size.x)
+    }
+
+    pub fn height(&self) -> Length {
+        mm(self.size.y)
+    }
+
+    /// Height of the top of the liquid.
+    pub fn surface(&self) -> Length {
+        self.height_of(self.vol())
+    }
+
+    /// Width of the inside of the vial at a height.
+    pub fn width_at(&self, height: Length) -> Length {
+        mm(self.width_at_mm(height.mm()))
+    }
+
+    pub(crate) fn width_at_mm(&self, height: f32) -> f32 {
+        let profile = self.shape.profile(self.size);
+        for pair in profile.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if height <= b.y && b.y > a.y {
+                let t = ((height - a.y) / (b.y - a.y)).clamp(0.0, 1.0);
+                return a.x + (b.x - a.x) * t;
+            }
+        }
+        profile.last().map(|p| p.x).unwrap_or(self.size.x)
+    }
+
+    /// Height that `volume` fills the vial to. In a [physical](Vial::physical)
+    /// vial the cross-section is a circle as wide as the vial. Otherwise the
+    /// cross-section is scaled so that `max_volume` fills it to the top.
+    /// Volumes above the top rise as if the top continued straight up.
+    pub fn height_of(&self, volume: Volume) -> Length {
+        mm(self.height_of_mm(volume))
+    }
+
+    fn height_of_mm(&self, volume: Volume) -> f32 {
+        let profile = self.shape.profile(self.size);
+        let mut rest = if self.physical {
+            volume.mm3().max(0.0)
+        } else {
+            let total: f32 = profile.windows(2).map(|p| self.segment_volume(p[0], p[1])).sum();
+            if self.max_volume <= ml(0.0) || total <= 0.0 {
+                return 0.0;
+            }
+            (volume / self.max_volume).max(0.0) as f32 * total
+        };
+        for pair in profile.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let segment = self.segment_volume(a, b);
+            if rest > segment {
+                rest -= segment;
+                continue;
+            }
+            let dy = b.y - a.y;
+            return a.y + self.segment_height(a.x, (b.x - a.x) / dy, rest).clamp(0.0, dy);
+        }
+        let top = self.cross_section(profile.last().map(|p| p.x).unwrap_or(self.size.x));
+        self.size.y + if top > 0.0 { rest / top } else { 0.0 }
+    }
+
+    /// Volume the vial holds to the top according to its geometry, as if it
+    /// were [physical](Vial::physical).
+    pub fn capacity(&self) -> Volume {
+        let vial = Vial { physical: true, ..self.clone() };
+        let profile = vial.shape.profile(vial.size);
+        let total: f32 = profile.windows(2).map(|p| vial.segment_volume(p[0], p[1])).sum();
+        units::mm3(total)
+    }
+
+    /// Area filled per mm of height where the vial is `width` wide (units:
+    /// mm^2 when physical, otherwise mm).
+    fn cross_section(&self, width: f32) -> f32 {
+        if self.physical {
+            PI * width * width / 4.0
+        } else {
+            width
+        }
+    }
+
+    /// Volume between two profile points.
+    fn segment_volume(&self, a: Vec2, b: Vec2) -> f32 {
+        let dy = b.y - a.y;
+        if self.physical {
+            // A conical frustum.
+            PI / 12.0 * dy * (a.x * a.x + a.x * b.x + b.x * b.x)
+        } else {
+            (a.x + b.x) / 2.0 * dy
+        }
+    }
+
+    /// Height into a segment that is `width` wide at its bottom and grows
+    /// by `k` per mm that `volume` fills.
+    fn segment_height(&self, width: f32, k: f32, volume: f32) -> f32 {
+        let bottom = self.cross_section(width);
+        if k.abs() < 1e-6 {
+            return if bottom > 0.0 { volume / bottom } else { 0.0 };
+        }
+        if self.physical {
+            // Solve pi / 12 ((w + k t)^3 - w^3) / k = volume for t.
+            ((width.powi(3) + 12.0 * k * volume / PI).max(0.0).cbrt() - width) / k
+        } else {
+            // Solve w t + k t^2 / 2 = volume for t.
+            ((width * width + 2.0 * k * volume).max(0.0).sqrt() - width) / k
+        }
+    }
+
+    /// Heights of the graduation marks and whether each is a major mark.
+    /// Every fifth mark is major.
+    pub fn graduations(&self) -> impl Iterator<Item = (Length, bool)> + '_ {
+        let step = self.graduation.filter(|g| *g > ml(0.0));
+        let count = step.map(|step| (self.max_volume / step + 0.001) as usize).unwrap_or(0);
+        (1..=count).map(move |i| (self.height_of(step.unwrap_or(ml(0.0)) * i as f64), i % 5 == 0))
+    }
+
+    pub fn vol(&self) -> Volume {
+        self.layers.iter().fold(ml(0.0), |sum, l| sum + l.volume())
     }
 
     pub fn discard_empties(&mut self) {
         if let Some(vol) = self.layers.last().map(|l| l.volume()) {
-            if abs_diff_eq!(vol, 0.0, epsilon = 0.01) {
+            if abs_diff_eq!(vol.ml(), 0.0, epsilon = 0.01) {
                 self.layers.pop();
             }
         }
     }
 
-    /// Pour self into other potion.
+    /// Pour at most `volume` of self into other potion.
+    pub fn pour_amount(&self, other: &Vial, volume: Volume) -> Option<Transfer> {
+        match self.pour(other)? {
+            Transfer::Liquid if volume > ml(0.0) => Some(Transfer::LiquidAmount(volume)),
+            Transfer::Liquid => None,
+            transfer => Some(transfer),
+        }
+    }
+
+    /// Pour self into other potion. Nothing pours past a solid top layer.
+    /// Liquid pours onto the same liquid or onto anything that doesn't flow;
+    /// powder pours into any vial with room and sinks through its liquid.
     pub fn pour(&self, other: &Vial) -> Option<Transfer> {
+        if self.layers.last().is_some_and(Layer::is_solid) {
+            return None;
+        }
+        let has_room = other.max_volume - other.vol() > ml(0.0);
         self.layers
             .last()
-            .and_then(|a| match &a {
-                &Layer::Liquid {
-                    id: color_a,
-                    ..
-                    // volume: volume_a,
-                } => other.layers.last().and_then(|b| match b {
-                    &Layer::Liquid {
-                        id: color_b,
-                        ..
-                        // volume: volume_b,
-                    } => {
-                        if *color_a == color_b {
-                            let empty_volume_b = other.max_volume - other.vol();
-                            if empty_volume_b > 0.0 {
-                                Some(Transfer::Liquid)
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    }
-                    // _ => None,
-                }).or((other.layers.is_empty()).then_some(Transfer::Liquid)),
-                // _ => todo!(),
+            .and_then(|a| match a {
+                Layer::Liquid { id: color_a, .. } => match other.layers.last() {
+                    Some(Layer::Liquid { id: color_b, .. }) => (color_a == color_b && has_room).then_some(Transfer::Liquid),
+                    _ => has_room.then_some(Transfer::Liquid),
+                },
+                Layer::Powder { .. } => has_room.then_some(Transfer::Liquid),
+                _ => None,
             })
             .or((!self.objects.is_empty()).then_some(Transfer::Object))
     }
@@ -354,20 +705,31 @@ impl Vial {
             None
         } else {
             let mut a = self.clone();
-            let mut additions = vec![];
-
-            let mut next_index = a.objects.len();
-            for obj in &mut a.objects {
-                if obj.flags.contains(ObjectFlags::BREAK) {
-                    obj.flags.remove(ObjectFlags::BREAK);
-                    obj.size /= 2.0;
-                    let mut o = obj.clone();
-                    o.id = UserData::object(next_index as u8).into();
-                    next_index += 1;
-                    additions.push(o);
+            let mut next_id = self.next_object_id();
+            let (broken, mut objects): (Vec<Object>, Vec<Object>) =
+                a.objects.drain(..).partition(|o| o.flags.contains(ObjectFlags::BREAK));
+            for obj in broken {
+                let mut fragments = obj.fragments();
+                // Keep the fragments inside the glass if they fit.
+                if let (Some(first), Some(last)) = (fragments.first(), fragments.last()) {
+                    let left = first.pos.x - first.extent().mm();
+                    let right = last.pos.x + last.extent().mm();
+                    let half = self.width_at(mm(obj.pos.y)).mm() / 2.0;
+                    let center = self.size.x / 2.0;
+                    let shift = if right - left > 2.0 * half {
+                        center - (left + right) / 2.0
+                    } else {
+                        (center - half - left).max(0.0) + (center + half - right).min(0.0)
+                    };
+                    fragments.iter_mut().for_each(|f| f.pos.x += shift);
+                }
+                for mut fragment in fragments {
+                    fragment.id = next_id;
+                    next_id += 1;
+                    objects.push(fragment);
                 }
             }
-            a.objects.extend(additions);
+            a.objects = objects;
             Some(Transition::BreakSeed(a))
         // if self.layers.len() == 0 {
         //     let mut a = self.clone();
@@ -398,32 +760,246 @@ impl Vial {
         }
     }
 
-    pub fn mix(&mut self, palette: &mut Palette) -> bool {
-        if self.layers.len() < 2 {
-            false
-        } else {
-            let Layer::Liquid {
-                id: top_id,
-                volume: top_volume,
-            } = self.layers.pop().unwrap();
-            let Layer::Liquid {
-                id: bottom_id,
-                volume: bottom_volume,
-            } = self.layers.pop().unwrap();
-            let top_color: Srgba = palette[top_id].clone().into();
-            let bottom_color: Srgba = palette[bottom_id].clone().into();
-            // let color = (top_volume * top_color + bottom_volume * bottom_color) / (top_volume + bottom_volume);
-            let p = bottom_volume / (top_volume + bottom_volume);
-            let color: Srgba = top_color.mix(&bottom_color, p);
-            let new_id = palette.len();
-            palette.push(color.into());
-            let mix = Layer::Liquid {
-                volume: top_volume + bottom_volume,
-                id: new_id,
+    /// Apply `rules` to the objects that have touched since the last call
+    /// and forget those contacts. Return whether the objects changed.
+    pub fn react(&mut self, rules: &[Rule]) -> bool {
+        let touches: Vec<(u64, u64)> = self
+            .objects
+            .iter_mut()
+            .flat_map(|o| {
+                let id = o.id;
+                o.touching.drain(..).map(move |other| (id, other))
+            })
+            .collect();
+        let mut changed = false;
+        for (a, b) in touches {
+            // Either may have gone in an earlier reaction.
+            let (Some(i), Some(j)) = (self.objects.iter().position(|o| o.id == a), self.objects.iter().position(|o| o.id == b)) else {
+                continue;
+            };
+            let Some((outcome, swap)) = rules
+                .iter()
+                .find_map(|r| r.matches(&self.objects[i], &self.objects[j]).map(|swap| (&r.outcome, swap)))
+            else {
+                continue;
             };
-            self.layers.push(mix);
-            true
+            let (i, j) = if swap { (j, i) } else { (i, j) };
+            match outcome {
+                Outcome::Merge(kind) => {
+                    let merged = rule::merge(&self.objects[i], &self.objects[j], *kind, self.next_object_id());
+                    self.objects.remove(i.max(j));
+                    self.objects.remove(i.min(j));
+                    self.objects.push(merged);
+                }
+                Outcome::Eat => {
+                    let food = self.objects.remove(j);
+                    let i = if i > j { i - 1 } else { i };
+                    rule::eat(&mut self.objects[i], &food);
+                }
+                Outcome::Wrap => {
+                    if self.objects[i].stuck_to.is_some() {
+                        continue;
+                    }
+                    self.objects[i].stuck_to = Some(self.objects[j].id);
+                }
+            }
+            changed = true;
+        }
+        if changed {
+            // Let go of objects that are gone.
+            let ids: HashSet<u64> = self.objects.iter().map(|o| o.id).collect();
+            for obj in &mut self.objects {
+                if obj.stuck_to.is_some_and(|id| !ids.contains(&id)) {
+                    obj.stuck_to = None;
+                }
+            }
         }
+        changed
+    }
+
+    /// Heat the vial and everything in it by `change` (units: °C), or cool
+    /// it if negative. Layers freeze, thaw and boil away as they go past
+    /// their liquids' freezing and boiling points. Return false if the
+    /// vial is empty.
+    pub fn heat(&mut self, change: f32) -> bool {
+        if self.layers.is_empty() && self.objects.is_empty() {
+            return false;
+        }
+        for layer in &mut self.layers {
+            layer.heat(change);
+        }
+        self.layers.retain(|l| l.volume() > ml(0.0));
+        for obj in &mut self.objects {
+            obj.temperature += change;
+        }
+        true
+    }
+
+    /// Boil the vial's layers for `dt` (units: s). Layers that boil dry are
+    /// gone. Return whether any liquid boiled away.
+    pub fn boil(&mut self, dt: f32) -> bool {
+        let mut changed = false;
+        for layer in &mut self.layers {
+            changed |= layer.boil(dt);
+        }
+        self.layers.retain(|l| l.volume() > ml(0.0));
+        changed
+    }
+
+    /// Let each object react for `dt` (units: s) to the layer it reaches.
+    /// Reactions run twice as fast for every [REACTION_DOUBLING] the object
+    /// is above room temperature. Return whether any object changed.
+    pub fn soak(&mut self, dt: f32) -> bool {
+        let reached: Vec<Option<usize>> = self
+            .objects
+            .iter()
+            .map(|o| match self.in_layer(o.pos, o.extent()) {
+                Some(VialLoc::Layer { index, .. }) => Some(index),
+                _ => None,
+            })
+            .collect();
+        let mut changed = false;
+        let mut dissolved = vec![];
+        let mut dyes = vec![];
+        for (obj, layer) in self.objects.iter_mut().zip(reached) {
+            if let Some(layer) = layer {
+                let warmth = self.layers[layer].temperature() - obj.temperature;
+                obj.temperature += warmth * (dt / WARM_TIME).min(1.0);
+            }
+            let effects: Vec<Effect> = match layer {
+                Some(layer) if self.layers[layer].is_liquid() => {
+                    let liquid = self.layers[layer].liquid().copied().unwrap_or_default();
+                    obj.reactions.iter().filter(|r| r.applies(&liquid)).map(|r| r.effect.clone()).collect()
+                }
+                _ => vec![],
+            };
+            if effects.is_empty() {
+                obj.soaked = 0.0;
+                continue;
+            }
+            let speed = 2f32.powf((obj.temperature - ROOM_TEMPERATURE) / REACTION_DOUBLING);
+            let dt = dt * speed;
+            obj.soaked += dt;
+            for effect in effects {
+                match effect {
+                    Effect::Swell { rate, max } => {
+                        if obj.size < max {
+                            obj.size = mm((obj.size.mm() + rate * dt).min(max.mm()));
+                            changed = true;
+                        }
+                    }
+                    Effect::Dissolve { rate, tint } => {
+                        let area = obj.shape.area(obj.size.mm());
+                        obj.size = mm((obj.size.mm() - rate * dt).max(0.0));
+                        changed = true;
+                        if let (Some(layer), Some(tint)) = (layer, tint) {
+                            let area = area - obj.shape.area(obj.size.mm());
+                            dyes.push((layer, tint, ml(DYE_STRENGTH * area)));
+                        }
+                        if obj.size <= mm(0.0) {
+                            dissolved.push(obj.id);
+                        }
+                    }
+                    Effect::Change { kind, after } => {
+                        if obj.soaked >= after && obj.kind != kind {
+                            obj.kind = kind;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        self.objects.retain(|o| !dissolved.contains(&o.id));
+        // Dye from the top down so new layers don't move those below.
+        dyes.sort_by_key(|(layer, ..)| std::cmp::Reverse(*layer));
+        for (layer, tint, volume) in dyes {
+            self.dye(layer, tint, volume);
+        }
+        self.layers.retain(|l| l.volume() > ml(0.0));
+        changed
+    }
+
+    /// Color `volume` of the liquid layer at `index` with palette color
+    /// `tint`. The dyed liquid lies on top of the rest of the layer, as if
+    /// poured in, until the two are mixed.
+    fn dye(&mut self, index: usize, tint: usize, volume: Volume) {
+        let Layer::Liquid { id, volume: undyed, liquid, temperature } = self.layers[index] else {
+            return;
+        };
+        if id == tint {
+            return;
+        }
+        let volume = units::min(volume, undyed);
+        *self.layers[index].volume_mut() = undyed - volume;
+        match self.layers.get_mut(index + 1) {
+            Some(Layer::Liquid { id, volume: dyed, liquid: other, .. }) if *id == tint && *other == liquid => {
+                *dyed = *dyed + volume;
+            }
+            _ => self.layers.insert(index + 1, Layer::Liquid { id: tint, volume, liquid, temperature }),
+        }
+    }
+
+    /// Dissolve powder for `dt` (units: s) into the liquid lying on it,
+    /// which grows denser by the mass it takes in but otherwise stays the
+    /// same liquid. Like reactions it runs
+    /// twice as fast for every [REACTION_DOUBLING] the liquid is above room
+    /// temperature. Return whether any powder dissolved.
+    pub fn dissolve(&mut self, dt: f32) -> bool {
+        let mut changed = false;
+        for i in 1..self.layers.len() {
+            let (below, above) = self.layers.split_at_mut(i);
+            let (Some(Layer::Powder { volume, powder, .. }), Some(Layer::Liquid { volume: liquid_volume, liquid, temperature, .. })) =
+                (below.last_mut(), above.first_mut())
+            else {
+                continue;
+            };
+            if powder.dissolves <= 0.0 || *liquid_volume <= ml(0.0) {
+                continue;
+            }
+            let speed = 2f32.powf((*temperature - ROOM_TEMPERATURE) / REACTION_DOUBLING);
+            let amount = units::min(ml(powder.dissolves * dt * speed), *volume);
+            *volume = *volume - amount;
+            let added = powder.density * amount.ml() / liquid_volume.ml();
+            liquid.density += added;
+            liquid.dissolved += added;
+            changed = true;
+        }
+        self.layers.retain(|l| l.volume() > ml(0.0));
+        changed
+    }
+
+    /// Mix the top two layers into one of a new color. Only liquids mix.
+    pub fn mix(&mut self, palette: &mut Palette) -> bool {
+        let [.., Layer::Liquid {
+            id: bottom_id,
+            volume: bottom_volume,
+            liquid: bottom_liquid,
+            temperature: bottom_temperature,
+        }, Layer::Liquid {
+            id: top_id,
+            volume: top_volume,
+            liquid: top_liquid,
+            temperature: top_temperature,
+        }] = *self.layers.as_slice()
+        else {
+            return false;
+        };
+        self.layers.truncate(self.layers.len() - 2);
+        let top_color: Srgba = palette[top_id].clone().into();
+        let bottom_color: Srgba = palette[bottom_id].clone().into();
+        // let color = (top_volume * top_color + bottom_volume * bottom_color) / (top_volume + bottom_volume);
+        let p = (bottom_volume / (top_volume + bottom_volume)) as f32;
+        let color: Srgba = top_color.mix(&bottom_color, p);
+        let new_id = palette.len();
+        palette.push(color.into());
+        let mix = Layer::Liquid {
+            volume: top_volume + bottom_volume,
+            id: new_id,
+            liquid: top_liquid.mix(&bottom_liquid, p),
+            temperature: top_temperature + (bottom_temperature - top_temperature) * p,
+        };
+        self.layers.push(mix);
+        true
     }
 }
 
@@ -446,20 +1022,17 @@ mod test {
     #[test]
     fn test_pour() {
         let a = Vial {
-                    layers: vec![Layer::Liquid {
-                        id: 0,
-                        volume: 50.0,
-                    }],
+                    layers: vec![Layer::water(0, ml(50.0))],
                     ..Default::default()
                 };
         let b = Vial::default();
-        assert_eq!(a.vol(), 50.0);
-        assert_eq!(b.vol(), 0.0);
+        assert_eq!(a.vol(), ml(50.0));
+        assert_eq!(b.vol(), ml(0.0));
         if let Some(transfer) = a.pour(&b) {
             assert!(matches!(transfer, Transfer::Liquid));
             if let Some((a, b)) = transfer.lerp(&a, &b, 1.0) {
-                assert_eq!(a.vol(), 0.0);
-                assert_eq!(b.vol(), 50.0);
+                assert_eq!(a.vol(), ml(0.0));
+                assert_eq!(b.vol(), ml(50.0));
             } else {
                 panic!();
             }
@@ -468,4 +1041,346 @@ mod test {
         };
 
     }
+
+    #[test]
+    fn test_height_of() {
+        let mut vial = Vial {
+            max_volume: ml(100.0),
+            size: Vec2::new(20.0, 80.0),
+            ..Default::default()
+        };
+        assert_eq!(vial.height_of(ml(50.0)), mm(40.0));
+        assert_eq!(vial.height_of(ml(125.0)), mm(100.0));
+        vial.shape = Shape::Tapered { bottom: 0.0 };
+        // A cone holds a quarter of its volume in the bottom half.
+        assert!((vial.height_of(ml(25.0)).mm() - 40.0).abs() < 1e-3);
+        assert_eq!(vial.width_at(mm(40.0)), mm(10.0));
+        vial.shape = Shape::RoundBottom;
+        let mut last = mm(0.0);
+        for volume in (0..=10).map(|i| i as f32 * 10.0) {
+            let height = vial.height_of(ml(volume));
+            assert!(height >= last);
+            last = height;
+        }
+        assert!((last.mm() - 80.0).abs() < 1e-3);
+        vial.layers = vec![Layer::water(0, ml(30.0)), Layer::water(1, ml(30.0))];
+        let Some(VialLoc::Layer { index: 1, height }) = vial.in_layer(Vec2::new(10.0, vial.height_of(ml(50.0)).mm()), mm(0.0)) else {
+            panic!("expected the second layer");
+        };
+        assert_eq!(height, vial.surface());
+    }
+
+    #[test]
+    fn test_physical_height() {
+        let mut vial = Vial {
+            size: Vec2::new(20.0, 80.0),
+            physical: true,
+            ..Default::default()
+        };
+        // A 20 mm wide cylinder rises 1000 / (100 pi) mm per ml.
+        assert!((vial.height_of(ml(10.0)).mm() - 100.0 / PI).abs() < 1e-3);
+        assert!((vial.capacity().ml() - 8.0 * PI).abs() < 1e-3);
+        assert!((vial.height_of(vial.capacity()).mm() - 80.0).abs() < 1e-3);
+        // max_volume no longer sets the scale.
+        vial.max_volume = ml(10.0);
+        assert!((vial.height_of(ml(10.0)).mm() - 100.0 / PI).abs() < 1e-3);
+        // A cone holds an eighth of its volume in the bottom half.
+        vial.shape = Shape::Tapered { bottom: 0.0 };
+        let capacity = vial.capacity();
+        assert!((capacity.ml() - 8.0 * PI / 3.0).abs() < 1e-3);
+        assert!((vial.height_of(capacity * 0.125).mm() - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pour_amount() {
+        let a = Vial {
+            layers: vec![Layer::water(0, ml(50.0))],
+            objects: vec![Object {
+                pos: Vec2::new(10.0, 5.0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let b = Vial::default();
+        let transfer = a.pour_amount(&b, ml(30.0)).expect("transfer");
+        let (a2, b2) = transfer.lerp(&a, &b, 0.5).unwrap();
+        assert_eq!(a2.vol(), ml(35.0));
+        assert_eq!(b2.vol(), ml(15.0));
+        let (a2, b2) = transfer.result(&a, &b);
+        assert_eq!(a2.vol(), ml(20.0));
+        assert_eq!(b2.vol(), ml(30.0));
+        // The object stays in the liquid that is left.
+        assert_eq!(a2.objects.len(), 1);
+
+        // Asking for more than there is pours everything.
+        let (a2, b2) = a.pour_amount(&b, ml(80.0)).unwrap().result(&a, &b);
+        assert!(a2.layers.is_empty());
+        assert_eq!(b2.vol(), ml(50.0));
+        assert_eq!(b2.objects.len(), 1);
+        assert!(a.pour_amount(&b, ml(0.0)).is_none());
+    }
+
+    #[test]
+    fn test_break_seed() {
+        use crate::{constant::WATER_DENSITY, ObjectKind};
+        let seed = Object {
+            pos: Vec2::new(2.5, 2.0),
+            size: mm(2.0),
+            id: 3,
+            flags: ObjectFlags::BREAK,
+            ..Default::default()
+        };
+        let other = Object { pos: Vec2::new(20.0, 1.0), size: mm(1.0), id: 1, ..Default::default() };
+        let vial = Vial { objects: vec![other, seed.clone()], ..Default::default() };
+        let Some(Transition::BreakSeed(broken)) = vial.transition() else {
+            panic!("no break");
+        };
+        assert_eq!(broken.objects.len(), 3);
+        let fragments = &broken.objects[1..];
+        let kinds: Vec<ObjectKind> = fragments.iter().map(|o| o.kind).collect();
+        assert_eq!(kinds, vec![ObjectKind::Shell, ObjectKind::Kernel]);
+        // The kernel floats and the shell sinks.
+        assert!(fragments[1].material.density < WATER_DENSITY && fragments[0].material.density > WATER_DENSITY);
+        // Ids are new and the mass is kept.
+        assert_eq!(broken.objects[0].id, 1);
+        assert!(fragments.iter().all(|o| o.id > 3) && fragments[0].id != fragments[1].id);
+        let mass: f32 = fragments.iter().map(Object::mass).sum();
+        assert!((mass - seed.mass()).abs() < 1e-6 * seed.mass());
+        // They don't overlap, are inside the glass and fly apart.
+        let (a, b) = (&fragments[0], &fragments[1]);
+        assert!(a.pos.distance(b.pos) >= (a.size + b.size).mm() - 1e-4);
+        assert!(a.pos.x - a.size.mm() >= -1e-4);
+        assert!(a.velocity.x < 0.0 && b.velocity.x > 0.0);
+        assert!(fragments.iter().all(|o| o.flags.is_empty()));
+        // Fragments don't break again.
+        assert!(fragments.iter().all(|o| !o.can_break()));
+    }
+
+    #[test]
+    fn test_unique_ids_across_breaks() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use std::collections::HashSet;
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            // Every vial starts with the same ids.
+            let mut vials: Vec<Vial> = (0..3)
+                .map(|_| Vial {
+                    objects: (0..3).map(|id| Object { size: mm(20.0), id, ..Default::default() }).collect(),
+                    ..Default::default()
+                })
+                .collect();
+            for _ in 0..30 {
+                // Pour objects across or break some.
+                let from = rng.gen_range(0..vials.len());
+                let into = rng.gen_range(0..vials.len());
+                if rng.gen_bool(0.3) && from != into {
+                    if let Some((a, b)) = Transfer::Object.lerp(&vials[from], &vials[into], 1.0) {
+                        vials[from] = a;
+                        vials[into] = b;
+                    }
+                } else {
+                    for obj in vials[from].objects.iter_mut().filter(|o| o.can_break()) {
+                        if rng.gen_bool(0.5) {
+                            obj.flags |= ObjectFlags::BREAK;
+                        }
+                    }
+                    if let Some(Transition::BreakSeed(v)) = vials[from].transition() {
+                        vials[from] = v;
+                    }
+                }
+                for vial in &vials {
+                    let unique: HashSet<u64> = vial.objects.iter().map(|o| o.id).collect();
+                    assert_eq!(unique.len(), vial.objects.len());
+                }
+            }
+            // Ids survive a save and load.
+            let text = ron::to_string(&vials).unwrap();
+            let loaded: Vec<Vial> = ron::from_str(&text).unwrap();
+            let ids = |vials: &[Vial]| vials.iter().flat_map(|v| &v.objects).map(|o| o.id).collect::<Vec<_>>();
+            assert_eq!(ids(&loaded), ids(&vials));
+        }
+    }
+
+    #[test]
+    fn test_react() {
+        use crate::ObjectKind;
+        let rules = vec![
+            Rule { kinds: [ObjectKind::Shell, ObjectKind::Kernel], outcome: Outcome::Merge(ObjectKind::Seed) },
+            Rule { kinds: [ObjectKind::Creature, ObjectKind::Seed], outcome: Outcome::Eat },
+            Rule { kinds: [ObjectKind::Plant, ObjectKind::Creature], outcome: Outcome::Wrap },
+        ];
+        let object = |kind, x: f32, id| Object { kind, pos: Vec2::new(x, 2.0), size: mm(1.0), id, ..Default::default() };
+        let mut vial = Vial {
+            objects: vec![
+                Object { touching: vec![3], ..object(ObjectKind::Kernel, 1.0, 1) },
+                Object { material: Material { density: 1_300.0, ..Material::SEED }, ..object(ObjectKind::Shell, 3.0, 2) },
+                Object { touching: vec![1], ..object(ObjectKind::Plant, 10.0, 3) },
+                object(ObjectKind::Creature, 12.0, 4),
+            ],
+            ..Default::default()
+        };
+        let mass: f32 = vial.objects.iter().map(Object::mass).sum();
+        // Touching objects with no rule for them are left alone.
+        assert!(!vial.react(&rules));
+        vial.objects[0].touching.push(2);
+        vial.objects[2].touching.push(4);
+        assert!(vial.react(&rules));
+        assert!(vial.objects.iter().all(|o| o.touching.is_empty()));
+        let kinds: Vec<ObjectKind> = vial.objects.iter().map(|o| o.kind).collect();
+        assert_eq!(kinds, vec![ObjectKind::Plant, ObjectKind::Creature, ObjectKind::Seed]);
+        let seed = &vial.objects[2];
+        // The heavier shell pulls the seed its way.
+        assert!(seed.id > 4 && seed.pos.x > 2.0);
+        assert_eq!(vial.objects[0].stuck_to, Some(4));
+        // The creature eats the seed and the plant keeps hold of it.
+        let seed = seed.id;
+        vial.objects[1].touching.push(seed);
+        assert!(vial.react(&rules));
+        assert_eq!(vial.objects.len(), 2);
+        assert_eq!(vial.objects[0].stuck_to, Some(4));
+        assert!(vial.objects[1].size > mm(1.0));
+        let total: f32 = vial.objects.iter().map(Object::mass).sum();
+        assert!((total - mass).abs() < 1e-4 * mass);
+    }
+
+    #[test]
+    fn test_soak() {
+        use crate::{ObjectKind, Reaction};
+        let seed = |x: f32, y: f32, id| Object {
+            pos: Vec2::new(x, y),
+            size: mm(1.0),
+            id,
+            reactions: vec![
+                Reaction::new(Liquid::WATER, Effect::Swell { rate: 0.5, max: mm(2.0) }),
+                Reaction::new(Liquid::WATER, Effect::Change { kind: ObjectKind::Plant, after: 3.0 }),
+                Reaction::new(Liquid::ACID, Effect::Dissolve { rate: 0.25, tint: Some(2) }),
+            ],
+            ..Default::default()
+        };
+        let mut vial = Vial {
+            layers: vec![
+                Layer::new(0, ml(20.0), Liquid::ACID),
+                Layer::water(1, ml(20.0)),
+            ],
+            objects: vec![seed(5.0, 2.0, 1), seed(10.0, 20.0, 2), seed(15.0, 70.0, 3)],
+            ..Default::default()
+        };
+        assert!(vial.soak(1.0));
+        let sizes: Vec<f32> = vial.objects.iter().map(|o| o.size.mm()).collect();
+        assert_eq!(sizes, vec![0.75, 1.5, 1.0]);
+        for _ in 0..3 {
+            vial.soak(1.0);
+        }
+        // The seed in acid is gone and has dyed as much of it as it
+        // covered; the one in water is full and has sprouted; the one
+        // above is as it was.
+        let ids: Vec<u64> = vial.objects.iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        let layers: Vec<usize> = vial.layers.iter().map(Layer::id).collect();
+        assert_eq!(layers, vec![0, 2, 1]);
+        assert!((vial.layers[1].volume().ml() - DYE_STRENGTH * PI).abs() < 1e-3);
+        assert_eq!(vial.layers[1].liquid(), Some(&Liquid::ACID));
+        assert!((vial.vol().ml() - 40.0).abs() < 1e-3);
+        assert_eq!(vial.objects[0].size, mm(2.0));
+        assert_eq!(vial.objects[0].kind, ObjectKind::Plant);
+        assert_eq!(vial.objects[1].kind, ObjectKind::Seed);
+        assert!(!vial.soak(1.0));
+        // Water with a drop of honey in it is still water.
+        assert!(Liquid::WATER.mix(&Liquid::HONEY, 0.001).is_like(&Liquid::WATER));
+        assert!(!Liquid::ACID.is_like(&Liquid::WATER));
+    }
+
+    #[test]
+    fn test_heat() {
+        let water = |id, volume, temperature| Layer::Liquid { id, volume: ml(volume), liquid: Liquid::WATER, temperature };
+        let mut vial = Vial { layers: vec![water(0, 50.0, ROOM_TEMPERATURE)], ..Default::default() };
+        for _ in 0..4 {
+            assert!(vial.heat(20.0));
+        }
+        assert!(vial.layers[0].is_boiling());
+        assert_eq!(vial.vol(), ml(50.0));
+        // Heat past boiling boils liquid away over time rather than
+        // warming it.
+        vial.heat(20.0);
+        assert_eq!(vial.vol(), ml(50.0));
+        assert!(vial.boil(1.0));
+        assert_eq!(vial.vol(), ml(50.0 - BOIL_RATE * BOIL_OFF));
+        assert!(vial.boil(10.0));
+        assert!(!vial.boil(1.0));
+        assert_eq!(vial.layers[0].temperature(), 100.0);
+        assert_eq!(vial.vol(), ml(50.0 - 20.0 * BOIL_OFF));
+        // Frozen, it can't be poured or mixed until it thaws.
+        vial.heat(-100.0);
+        assert!(vial.layers[0].is_solid() && !vial.layers[0].is_boiling());
+        assert!(vial.pour(&Vial::default()).is_none());
+        vial.layers.push(water(1, 10.0, 5.0));
+        assert!(!vial.mix(&mut Palette(vec![])));
+        vial.layers.pop();
+        vial.heat(20.0);
+        assert!(!vial.layers[0].is_solid());
+        assert!(vial.pour(&Vial::default()).is_some());
+        // Pouring evens out the temperature by volume.
+        let hot = Vial { layers: vec![water(0, 10.0, 80.0)], ..Default::default() };
+        let cold = Vial { layers: vec![water(0, 30.0, 0.5)], ..Default::default() };
+        let (_, mixed) = hot.pour(&cold).unwrap().lerp(&hot, &cold, 1.0).unwrap();
+        assert!((mixed.layers[0].temperature() - 20.375).abs() < 1e-3);
+        // Warm objects react faster.
+        let seed = |temperature| Object {
+            pos: Vec2::new(12.5, 2.0),
+            size: mm(1.0),
+            temperature,
+            reactions: vec![crate::Reaction::new(Liquid::WATER, Effect::Swell { rate: 0.1, max: mm(5.0) })],
+            ..Default::default()
+        };
+        let mut warm = Vial { layers: vec![water(0, 50.0, 40.0)], objects: vec![seed(40.0)], ..Default::default() };
+        let mut cool = Vial { objects: vec![seed(ROOM_TEMPERATURE)], ..warm.clone() };
+        cool.layers = vec![water(0, 50.0, ROOM_TEMPERATURE)];
+        warm.soak(1.0);
+        cool.soak(1.0);
+        assert!((warm.objects[0].size.mm() - 1.4).abs() < 1e-4);
+        assert!((cool.objects[0].size.mm() - 1.1).abs() < 1e-4);
+        // A frozen layer can be written by hand like any other.
+        let ice: Layer = ron::from_str(r#"Frozen(id: 0, volume: "10 ml")"#).unwrap();
+        assert!(ice.is_solid() && ice.temperature() == ROOM_TEMPERATURE);
+    }
+
+    #[test]
+    fn test_powder() {
+        let water = Layer::water(0, ml(20.0));
+        let salt = |volume| Layer::Powder { id: 1, volume: ml(volume), powder: Powder::SALT, temperature: ROOM_TEMPERATURE };
+        // Powder sinks below the liquid it is poured into and joins the
+        // same powder there.
+        let shaker = Vial { layers: vec![salt(5.0)], ..Default::default() };
+        let glass = Vial { layers: vec![salt(2.0), water.clone()], ..Default::default() };
+        let (shaker, glass) = shaker.pour(&glass).unwrap().result(&shaker, &glass);
+        assert!(shaker.layers.is_empty());
+        assert_eq!(glass.layers.len(), 2);
+        assert!(matches!(glass.layers[0], Layer::Powder { .. }) && glass.layers[0].volume() == ml(7.0));
+        // Liquid pours onto powder as it would into an empty vial.
+        let jug = Vial { layers: vec![water.clone()], ..Default::default() };
+        let sand = Vial { layers: vec![salt(5.0)], ..Default::default() };
+        let (_, sand) = jug.pour(&sand).unwrap().result(&jug, &sand);
+        assert!(sand.layers[1].is_liquid());
+        // A solid stays put.
+        let rock = Vial {
+            layers: vec![Layer::Solid { id: 1, volume: ml(5.0), material: Material::GLASS, temperature: ROOM_TEMPERATURE }],
+            ..Default::default()
+        };
+        assert!(rock.pour(&Vial::default()).is_none());
+        // Nothing pours into a full vial, even when asked to directly.
+        let full = Vial { layers: vec![Layer::water(0, ml(100.0))], ..Default::default() };
+        assert!(Transfer::Liquid.lerp(&jug, &full, 0.5).is_none());
+        // Salt dissolves into the water on it, which grows denser.
+        let mut glass = glass;
+        assert!(glass.dissolve(2.0));
+        assert!((glass.layers[0].volume().ml() - 6.0).abs() < 1e-4);
+        let density = glass.layers[1].liquid().unwrap().density;
+        assert!((density - (Liquid::WATER.density + Powder::SALT.density / 20.0)).abs() < 1e-2, "{}", density);
+        while glass.dissolve(1.0) {}
+        assert_eq!(glass.layers.len(), 1);
+        // Salt water is still water to what reacts to it.
+        let salted = glass.layers[0].liquid().copied().unwrap();
+        assert!(salted.density > 1.1 * Liquid::WATER.density);
+        assert!(crate::Reaction::new(Liquid::WATER, Effect::Swell { rate: 0.1, max: mm(5.0) }).applies(&salted));
+    }
 }
@@ -1,11 +1,14 @@
-use super::{Object, Vial, VialLoc, ObjectFlags};
+use super::{Layer, Liquid, Object, Vial, ObjectFlags, ObjectShape};
 use crate::constant::*;
+use crate::object_shape::polygon_area_below;
 use bevy_math::Vec2;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::f32::consts::PI;
 use crossbeam::channel::{Receiver, TryRecvError};
 use rand::{prelude::*, distributions::{Uniform}};
 use crate::user_data::UserData;
+use crate::units::{self, mm, Area, AreaExt, Length, LengthExt, Volume, VolumeExt};
+use crate::wave::Waves;
 
 use rapier2d::prelude::*;
 
@@ -22,17 +25,45 @@ pub struct VialPhysics {
     multibody_joint_set: MultibodyJointSet,
     ccd_solver: CCDSolver,
     query_pipeline: QueryPipeline,
-    objects: HashMap<u128, RigidBodyHandle>,
+    objects: HashMap<u64, RigidBodyHandle>,
     collision_recv: Receiver<CollisionEvent>,
     contact_force_recv: Receiver<ContactForceEvent>,
     physics_hooks: (),
     event_handler: ChannelEventCollector,
+    size: Vec2,
+    waves: Option<Waves>,
+    /// Velocity of each body before the last step, to measure impacts.
+    velocities: HashMap<RigidBodyHandle, Vector<Real>>,
+    /// Joints holding objects to those they are stuck to, by the id of
+    /// the stuck object.
+    joints: HashMap<u64, ImpulseJointHandle>,
+    /// Collider on top of the layers that don't flow and the height it was
+    /// made for (units: mm).
+    floor: Option<(ColliderHandle, f32)>,
+    /// Objects held where they were when the layer around them stopped
+    /// flowing, by id.
+    held: HashSet<u64>,
 }
 
 
 const GROUND_ID: u8 = 1;
 const LEFT_WALL_ID: u8 = 2;
 const RIGHT_WALL_ID: u8 = 3;
+/// Thickness of the glass (units: mm).
+const WALL_THICKNESS: f32 = 5.0;
+
+/// Step, as a fraction of an object's size or in radians, over which the
+/// change in its buoyancy is measured.
+const PROBE: f32 = 0.01;
+
+/// Columns in the surface waves of a vial.
+const WAVE_COLUMNS: usize = 24;
+/// How hard an object entering a vial pushes the surface down (units:
+/// mm/s).
+const ENTER_SPLASH: f32 = 80.0;
+/// How much of the vertical speed of an object crossing the surface goes
+/// into the waves each second.
+const WAVE_COUPLING: f32 = 3.0;
 
 impl VialPhysics {
     pub fn new(vial: &Vial) -> Self {
@@ -41,36 +72,26 @@ impl VialPhysics {
         let event_handler = ChannelEventCollector::new(collision_send, contact_force_send);
         let rigid_body_set = RigidBodySet::new();
         let mut collider_set = ColliderSet::new();
-        // Convert from mm to m.
-        let vial_size_m = vial.size * MM_TO_M;
-
-        let wall_width_m = 10.0 * MM_TO_M;
-
-        /* Create the ground. */
-        let collider = ColliderBuilder::cuboid(vial_size_m.x, wall_width_m)
-            .translation(vector![0.0, -wall_width_m])
-            .user_data(UserData::wall(GROUND_ID).into())
-            .build();
-        collider_set.insert(collider);
-
-        // Walls
-
-        // Left wall
-        let collider = ColliderBuilder::cuboid(wall_width_m, vial_size_m.y)
-            .translation(vector![-wall_width_m, vial_size_m.y / 2.0 - wall_width_m])
-            .user_data(UserData::wall(LEFT_WALL_ID).into())
-            .build();
-        collider_set.insert(collider);
-
-        // Right wall
-        let collider = ColliderBuilder::cuboid(wall_width_m, vial_size_m.y)
-            .translation(vector![
-                vial_size_m.x + wall_width_m,
-                vial_size_m.y / 2.0 - wall_width_m
-            ])
-            .user_data(UserData::wall(RIGHT_WALL_ID).into())
-            .build();
-        collider_set.insert(collider);
+        // Each segment of glass is a slab behind it so that small bodies
+        // pressed against it are pushed back inside.
+        for (id, points) in wall_polylines(vial) {
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let d = b - a;
+                // The outline runs with the inside on its left.
+                let out = Vec2::new(d.y, -d.x).normalize_or_zero() * WALL_THICKNESS;
+                let vertices: Vec<Point<Real>> =
+                    [a, b, b + out, a + out].iter().map(|p| to_m(*p).into()).collect();
+                if let Some(collider) = ColliderBuilder::convex_hull(&vertices) {
+                    let collider = collider
+                        .friction(vial.material.friction)
+                        .restitution(vial.material.restitution)
+                        .restitution_combine_rule(CoefficientCombineRule::Multiply)
+                        .user_data(UserData::wall(id).into());
+                    collider_set.insert(collider.build());
+                }
+            }
+        }
 
         /* Create other structures necessary for the simulation. */
         let integration_parameters = IntegrationParameters {
@@ -104,33 +125,109 @@ impl VialPhysics {
             event_handler,
             collision_recv,
             contact_force_recv,
+            size: vial.size,
+            waves: None,
+            velocities: HashMap::new(),
+            joints: HashMap::new(),
+            floor: None,
+            held: HashSet::new(),
         };
 
-        for obj in &vial.objects {
-            vial_physics.insert(obj);
-        }
+        vial_physics.sync(vial);
         vial_physics
     }
 
+    /// Simulate waves on the surface of the liquid.
+    pub fn with_waves(mut self) -> Self {
+        self.waves = Some(Waves::new(self.size.x, WAVE_COLUMNS));
+        self
+    }
+
+    pub fn waves(&self) -> Option<&Waves> {
+        self.waves.as_ref()
+    }
+
+    /// Push the surface at `x` (units: mm) at `speed` (units: mm/s) if
+    /// there are waves.
+    pub fn splash(&mut self, x: f32, speed: f32) {
+        if let Some(waves) = &mut self.waves {
+            waves.push(x, speed);
+        }
+    }
+
+    /// Size of the inside of the vial (units: mm).
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    /// Time step (units: s).
+    pub fn dt(&self) -> f32 {
+        self.integration_parameters.dt
+    }
+
+    pub(crate) fn bodies(&self) -> &RigidBodySet {
+        &self.rigid_body_set
+    }
+
+    pub(crate) fn bodies_mut(&mut self) -> &mut RigidBodySet {
+        &mut self.rigid_body_set
+    }
+
+    /// Resolve contacts between bodies a few mm across firmly enough that
+    /// tall piles of them don't sink into each other.
+    pub(crate) fn stiffen_contacts(&mut self) {
+        let parameters = &mut self.integration_parameters;
+        parameters.length_unit = 0.01;
+        parameters.num_solver_iterations = std::num::NonZeroUsize::new(8).unwrap();
+        parameters.contact_natural_frequency = 240.0;
+    }
+
+    /// Add a frictionless ball for a particle of liquid at `pos` (units: mm).
+    pub(crate) fn insert_particle(&mut self, pos: Vec2, radius: Length, density: f32, user_data: UserData) -> RigidBodyHandle {
+        let user_data: u128 = user_data.into();
+        let mut rigid_body = RigidBodyBuilder::dynamic().translation(to_m(pos)).ccd_enabled(true).build();
+        rigid_body.user_data = user_data;
+        let collider = ColliderBuilder::ball(radius.m())
+            .friction(0.0)
+            .restitution(0.0)
+            .density(density)
+            .user_data(user_data)
+            .build();
+        let handle = self.rigid_body_set.insert(rigid_body);
+        self.collider_set.insert_with_parent(collider, handle, &mut self.rigid_body_set);
+        handle
+    }
+
+    pub(crate) fn remove_body(&mut self, handle: RigidBodyHandle) {
+        self.rigid_body_set.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            true,
+        );
+    }
+
     pub fn insert(&mut self, obj: &Object) -> bool {
-        if let std::collections::hash_map::Entry::Vacant(e) = self.objects.entry(obj.id as u128) {
+        if let std::collections::hash_map::Entry::Vacant(e) = self.objects.entry(obj.id) {
             // dbg!(obj);
-            let pos_m = obj.pos * MM_TO_M;
-            // dbg!(pos_m);
             let mut rigid_body = RigidBodyBuilder::dynamic()
-                .translation(vector![pos_m.x, pos_m.y])
+                .translation(to_m(obj.pos))
+                .rotation(obj.angle)
+                .linvel(to_m(obj.velocity))
                 .ccd_enabled(true)
                 .build();
-            rigid_body.user_data = obj.id as u128;
+            rigid_body.user_data = UserData::object(obj.id).into();
 
-            let mut collider = ColliderBuilder::ball(obj.size * MM_TO_M)
-                .restitution(0.7)
+            let mut collider = shape_collider(&obj.shape, obj.size)
+                .density(obj.material.density)
+                .friction(obj.material.friction)
+                .restitution(obj.material.restitution)
+                .restitution_combine_rule(CoefficientCombineRule::Multiply)
                 .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
-                // .restitution(0.1)
                 .build();
-            // collider.set_density(GLASS_DENSITY); // glass
-            collider.set_density(WATER_DENSITY); // glass
-            collider.user_data = obj.id as u128;
+            collider.user_data = UserData::object(obj.id).into();
             let handle = self.rigid_body_set.insert(rigid_body);
             e.insert(handle);
             self.collider_set
@@ -141,69 +238,287 @@ impl VialPhysics {
         }
     }
 
+    /// Add bodies for the vial's new objects and drop those of objects it
+    /// no longer has. Objects whose mass changed get a new collider and
+    /// objects stuck to another are joined to it.
+    pub fn sync(&mut self, vial: &Vial) {
+        for obj in &vial.objects {
+            if !self.insert(obj) {
+                self.refit(obj);
+            }
+        }
+        let gone: Vec<u64> = self
+            .objects
+            .keys()
+            .filter(|id| !vial.objects.iter().any(|o| o.id == **id))
+            .copied()
+            .collect();
+        for id in gone {
+            self.joints.remove(&id);
+            self.held.remove(&id);
+            if let Some(handle) = self.objects.remove(&id) {
+                self.remove_body(handle);
+            }
+        }
+        for obj in &vial.objects {
+            self.stick(obj);
+        }
+        self.fit_floor(vial);
+    }
+
+    /// Put a false floor on top of the vial's frozen, powder and solid
+    /// layers, or move it when they have grown or shrunk. Objects land on
+    /// it as they would on the glass.
+    fn fit_floor(&mut self, vial: &Vial) {
+        let top = vial.layer_heights().take(vial.settled()).last().map(|(layer, _, top)| (layer, top.mm()));
+        if self.floor.map(|(_, height)| height) == top.map(|(_, height)| height) {
+            return;
+        }
+        if let Some((handle, _)) = self.floor.take() {
+            self.collider_set.remove(handle, &mut self.island_manager, &mut self.rigid_body_set, true);
+        }
+        self.hold(top.map(|(_, height)| height));
+        let Some((layer, height)) = top else {
+            return;
+        };
+        let half = vial.width_at(mm(height)).mm() / 2.0;
+        let center = vial.size.x / 2.0;
+        let vertices: Vec<Point<Real>> = [
+            Vec2::new(center - half, height),
+            Vec2::new(center + half, height),
+            Vec2::new(center + half, height - WALL_THICKNESS),
+            Vec2::new(center - half, height - WALL_THICKNESS),
+        ]
+        .iter()
+        .map(|p| to_m(*p).into())
+        .collect();
+        // Powder takes the bounce out of what lands on it.
+        let (friction, restitution) = match layer {
+            Layer::Solid { material, .. } => (material.friction, material.restitution),
+            Layer::Powder { .. } => (vial.material.friction, 0.0),
+            _ => (vial.material.friction, vial.material.restitution),
+        };
+        if let Some(collider) = ColliderBuilder::convex_hull(&vertices) {
+            let collider = collider
+                .friction(friction)
+                .restitution(restitution)
+                .restitution_combine_rule(CoefficientCombineRule::Multiply)
+                .user_data(UserData::wall(GROUND_ID).into());
+            self.floor = Some((self.collider_set.insert(collider.build()), height));
+        }
+    }
+
+    /// Hold objects whose centers are below a new floor at `height` (units:
+    /// mm) where they are, as if set in the layer, and lift those it only
+    /// overlaps onto it rather than let them be shoved out. Let go of held
+    /// objects once the floor is below them.
+    fn hold(&mut self, height: Option<f32>) {
+        let floor = height.map_or(f32::NEG_INFINITY, |h| mm(h).m());
+        for (id, handle) in &self.objects {
+            let Some(body) = self.rigid_body_set.get_mut(*handle) else {
+                continue;
+            };
+            if body.translation().y < floor {
+                if self.held.insert(*id) {
+                    body.set_body_type(RigidBodyType::Fixed, true);
+                }
+                continue;
+            }
+            if self.held.remove(id) {
+                body.set_body_type(RigidBodyType::Dynamic, true);
+            }
+            let bottom = body
+                .colliders()
+                .iter()
+                .filter_map(|c| self.collider_set.get(*c))
+                .map(|c| c.compute_aabb().mins.y)
+                .fold(f32::INFINITY, f32::min);
+            if bottom < floor {
+                let lifted = body.translation() + vector![0.0, floor - bottom];
+                body.set_translation(lifted, true);
+            }
+        }
+    }
+
+    /// Fit the collider of an object to its size if it has grown or shrunk.
+    fn refit(&mut self, obj: &Object) {
+        let Some(body) = self.objects.get(&obj.id).and_then(|h| self.rigid_body_set.get(*h)) else {
+            return;
+        };
+        for handle in body.colliders() {
+            let Some(collider) = self.collider_set.get_mut(*handle) else {
+                continue;
+            };
+            if (collider.mass() - obj.mass()).abs() > 1e-3 * obj.mass() {
+                collider.set_shape(shape_collider(&obj.shape, obj.size).shape);
+                collider.set_density(obj.material.density);
+            }
+        }
+    }
+
+    /// Join an object to the one it is stuck to, where they are now, or
+    /// undo the joint if it has let go.
+    fn stick(&mut self, obj: &Object) {
+        let target = obj.stuck_to.and_then(|id| self.objects.get(&id)).copied();
+        match (target, self.joints.get(&obj.id)) {
+            (Some(_), Some(_)) => (),
+            (Some(target), None) => {
+                let (Some(handle), Some(other)) = (self.objects.get(&obj.id).copied(), self.rigid_body_set.get(target)) else {
+                    return;
+                };
+                let Some(body) = self.rigid_body_set.get(handle) else {
+                    return;
+                };
+                let frame = body.position().inverse() * other.position();
+                let joint = FixedJointBuilder::new().local_frame1(frame).build();
+                let joint = self.impulse_joint_set.insert(handle, target, joint, true);
+                self.joints.insert(obj.id, joint);
+            }
+            (None, Some(_)) => {
+                if let Some(joint) = self.joints.remove(&obj.id) {
+                    self.impulse_joint_set.remove(joint, true);
+                }
+            }
+            (None, None) => (),
+        }
+    }
+
     pub fn kick_on_enter(&mut self, vial: &mut Vial) {
         let mut rng = rand::thread_rng();
         let kick_range = Uniform::from(-0.1..1.0);
-        let mut map: HashMap<u128, &mut Object> =
-            vial.objects.iter_mut().map(|o| (o.id as u128, o)).collect();
+        let mut map: HashMap<u64, &mut Object> =
+            vial.objects.iter_mut().map(|o| (o.id, o)).collect();
         for (_handle, rigid_body) in self.rigid_body_set.iter_mut() {
-            if let Some(obj) = map.remove(&rigid_body.user_data) {
+            if let Some(obj) = object_id(rigid_body.user_data).and_then(|id| map.remove(&id)) {
                 if obj.flags.contains(ObjectFlags::ENTER_VIAL) {
                     let x: f32 = rng.sample(kick_range);
                     rigid_body.set_linvel(vector![x, 0.0], true);
                     obj.flags.remove(ObjectFlags::ENTER_VIAL);
+                    if let Some(waves) = &mut self.waves {
+                        waves.push(obj.pos.x, -ENTER_SPLASH);
+                    }
                 }
             }
         }
     }
 
     pub fn add_buoyancy_forces(&mut self, vial: &Vial) {
-        let mut map: HashMap<u128, &Object> =
-            vial.objects.iter().map(|o| (o.id as u128, o)).collect();
+        self.fit_floor(vial);
+        let mut map: HashMap<u64, &Object> =
+            vial.objects.iter().map(|o| (o.id, o)).collect();
+        let dt = self.integration_parameters.dt;
         for (_handle, rigid_body) in self.rigid_body_set.iter_mut() {
             rigid_body.reset_forces(true);
+            rigid_body.reset_torques(true);
             let p: &Vector<Real> = rigid_body.translation();
             // let p = r.translation;
             let v = rigid_body.velocity_at_point(&Point::from(*p));
 
-            if let Some(obj) = map.remove(&rigid_body.user_data) {
-                let s = obj.size * MM_TO_M;
-                let pos_mm = Vec2::new(p.x * M_TO_MM, p.y * M_TO_MM + obj.size);
-                match vial.in_layer(pos_mm, obj.size) {
-                    Some(VialLoc::Layer {
-                        index: _i,
-                        height: layer_height,
-                    }) => {
-                        if let Some(buoyancy_area) =
-                            circle_buoyancy_area(s, p, &vector![0.0, 1.0], layer_height * MM_TO_M)
-                        {
-                            let buoyancy_force =
-                                vector![0.0, (buoyancy_area * GRAVITY * WATER_DENSITY)];
-                            rigid_body.add_force(buoyancy_force, false);
-                            // rigid_body.set_linear_damping(200.0);
-                            // rigid_body.set_linear_damping(dbg!(drag_force(WATER_DENSITY, 1.0, 2.0 * s, CIRCLE_DRAG)));
-                            let fudge = 1.0;
-                            rigid_body.set_linear_damping(
-                                fudge * drag_force(WATER_DENSITY, v.y.abs(), 2.0 * s, CIRCLE_DRAG),
-                            );
-                            // rigid_body.set_linear_damping(fudge * drag_force(WATER_DENSITY, v.y * v.y, 2.0 * s, CIRCLE_DRAG));
-                        }
+            if let Some(obj) = object_id(rigid_body.user_data).and_then(|id| map.remove(&id)) {
+                // Sum buoyancy and blend drag over every layer the object
+                // is in, each by the area submerged in it.
+                let s = obj.size;
+                let area = units::m2(obj.shape.area(s.m()));
+                let extent = obj.extent();
+                let mass = rigid_body.mass();
+                let angle = rigid_body.rotation().angle();
+                let x = to_mm(p).x;
+                let offset = self.waves.as_ref().map_or(0.0, |w| w.offset_at(x));
+                // Liquid, bottom and top of each liquid layer. The top of the
+                // liquid rises and falls with the waves.
+                let count = vial.layers.len();
+                let layers: Vec<(&Liquid, Length, Length)> = vial
+                    .layer_heights()
+                    .enumerate()
+                    .filter(|(_, (layer, _, _))| layer.is_liquid())
+                    .filter_map(|(i, (layer, bottom, top))| {
+                        let top = if i + 1 == count { top + mm(offset) } else { top };
+                        Some((layer.liquid()?, bottom, top))
+                    })
+                    .collect();
+                // Objects moving through the surface make waves.
+                if let (Some(waves), Some((_, _, top))) = (&mut self.waves, layers.last()) {
+                    if units::abs_diff(units::m(p.y), *top) < extent {
+                        waves.push(x, v.y * units::m(1.0).mm() * WAVE_COUPLING * dt);
                     }
-                    Some(VialLoc::Top {
-                        height: layer_height,
-                    }) => {
-                        if let Some(buoyancy_area) =
-                            circle_buoyancy_area(s, p, &vector![0.0, 1.0], layer_height * MM_TO_M)
-                        {
-                            assert!(buoyancy_area <= 0.01, "{}", buoyancy_area);
+                }
+                // Outline about the center as the object lies and as it
+                // would lie turned a little further.
+                let turn = PROBE;
+                let outline: Vec<Vec2> = if obj.shape.is_circle() { vec![] } else { obj.shape.outline(s.m(), angle) };
+                let turned: Vec<Vec2> = outline.iter().map(|q| Vec2::from_angle(turn).rotate(*q)).collect();
+                // Area submerged in each layer with its moment about the
+                // center, for the object with `outline` raised by `rise`.
+                let none = (units::m2(0.0), units::m(0.0) * units::m2(0.0));
+                let submerged = |outline: &[Vec2], rise: Length| -> Vec<(Area, Volume)> {
+                    let center = p + vector![0.0, rise.m()];
+                    let below = |w: Length| {
+                        if obj.shape.is_circle() {
+                            let a = circle_buoyancy_area(s, &center, &vector![0.0, 1.0], w);
+                            a.map_or(none, |a| (a, none.1))
+                        } else {
+                            polygon_area_below(outline, (w - units::m(center.y)).m())
+                                .map_or(none, |(a, x)| (units::m2(a), units::m(x) * units::m2(a)))
                         }
+                    };
+                    layers
+                        .iter()
+                        .map(|(_, bottom, top)| {
+                            let ((top_area, top_moment), (bottom_area, bottom_moment)) =
+                                (below(*top), below(*bottom));
+                            (top_area - bottom_area, top_moment - bottom_moment)
+                        })
+                        .collect()
+                };
+                // Lift acts at the centroid of what is submerged, which
+                // turns long objects to lie flat.
+                let buoyancy = |parts: &[(Area, Volume)]| -> (f32, f32) {
+                    parts.iter().zip(&layers).fold(
+                        (0.0, 0.0),
+                        |(lift, torque), ((a, m), (liquid, _, _))| {
+                            let pressure = GRAVITY * liquid.density;
+                            (lift + a.m2() * pressure, torque + m.m3() * pressure)
+                        },
+                    )
+                };
+                let parts = submerged(&outline, units::m(0.0));
+                let (lift, torque) = buoyancy(&parts);
+                // How fast lift falls as the object rises and torque as it
+                // turns. Applied as they are, these springs make light, thin
+                // objects bob ever higher, so they are damped implicitly.
+                let rise = s * PROBE as f64;
+                let stiffness = ((lift - buoyancy(&submerged(&outline, rise)).0) / rise.m()).max(0.0);
+                let twist = ((torque - buoyancy(&submerged(&turned, units::m(0.0))).1) / turn).max(0.0);
+                let inertia = rigid_body.mass_properties().local_mprops.principal_inertia();
+                // Drag goes by the width the object shows the flow and, as
+                // it turns, by the speed of its ends.
+                let width = match Vec2::new(v.x, v.y).try_normalize() {
+                    Some(dir) if !obj.shape.is_circle() => {
+                        let across: Vec<f32> = outline.iter().map(|q| q.dot(dir.perp())).collect();
+                        units::m(across.iter().copied().fold(f32::NEG_INFINITY, f32::max)
+                            - across.iter().copied().fold(f32::INFINITY, f32::min))
                     }
-                    _ => {
-                        // let m = rigid_body.mass();
-                        rigid_body.set_linear_damping(0.0);
-                        // rigid_body.add_force(vector![0.0, -m * GRAVITY], false);
+                    _ => s * 2.0,
+                };
+                // Circles settle as they always have: by drag alone and
+                // free to roll.
+                let shaped = !obj.shape.is_circle();
+                let spin = rigid_body.angvel().abs() * extent.m();
+                let mut damping = if shaped { stiffness * dt / mass } else { 0.0 };
+                let mut angular_damping = if shaped && inertia > 0.0 { twist * dt / inertia } else { 0.0 };
+                for ((a, _), (liquid, _, _)) in parts.iter().zip(&layers) {
+                    // Damping is applied implicitly so it stays stable
+                    // however viscous the liquid is.
+                    let fraction = (*a / area) as f32;
+                    damping += fraction * drag_damping(liquid, v.norm(), width, mass);
+                    if shaped {
+                        angular_damping += fraction * drag_damping(liquid, spin, extent * 2.0, mass);
                     }
                 }
+                rigid_body.add_force(vector![0.0, lift], false);
+                rigid_body.add_torque(torque, false);
+                rigid_body.set_linear_damping(damping);
+                rigid_body.set_angular_damping(angular_damping);
             } else {
                 // let m = rigid_body.mass();
                 rigid_body.set_linear_damping(0.0);
@@ -219,6 +534,9 @@ impl VialPhysics {
         /* Run the game loop, stepping the simulation once per frame. */
         // self.integration_parameters.dt = dt;
         // while accum < dt {
+        self.velocities = self.objects.values()
+            .filter_map(|h| self.rigid_body_set.get(*h).map(|b| (*h, *b.linvel())))
+            .collect();
         self.physics_pipeline.step(
             &gravity,
             &self.integration_parameters,
@@ -234,10 +552,13 @@ impl VialPhysics {
             &self.physics_hooks,
             &self.event_handler,
         );
+        if let Some(waves) = &mut self.waves {
+            waves.step(self.integration_parameters.dt);
+        }
     }
 
     #[allow(unused_assignments)]
-    pub fn handle_collisions(&mut self, objects: &mut HashMap<u128, &mut Object>) -> Result<(), TryRecvError> {
+    pub fn handle_collisions(&mut self, objects: &mut HashMap<u64, &mut Object>) -> Result<(), TryRecvError> {
         use rapier2d::geometry::CollisionEvent::*;
         loop {
             let event = self.collision_recv.try_recv();
@@ -256,16 +577,22 @@ impl VialPhysics {
                             (a, b) = (b, a);
                             (user_data_a, user_data_b) = (user_data_b, user_data_a);
                         }
-                        if user_data_a.is_object() && user_data_b.is_wall() && user_data_b.id == GROUND_ID {
-                            if let Some(obj) = objects.get_mut(&a.user_data) {
-                                if obj.flags.contains(ObjectFlags::EXPECT_BREAK) {
-                                    obj.flags.remove(ObjectFlags::EXPECT_BREAK);
-                                    obj.flags.insert(ObjectFlags::BREAK);
-                                }
+                        // The solver's guess is settled once the object
+                        // lands; whether it broke is up to the impact.
+                        if user_data_a.is_object() && user_data_b.is_wall() && user_data_b.id == GROUND_ID.into() {
+                            if let Some(obj) = objects.get_mut(&user_data_a.id) {
+                                obj.flags.remove(ObjectFlags::EXPECT_BREAK);
                             } else {
                                 continue;
                             }
                         }
+                        // Leave it to the level's rules what comes of two
+                        // objects touching.
+                        if user_data_a.is_object() && user_data_b.is_object() {
+                            if let Some(obj) = objects.get_mut(&user_data_a.id) {
+                                obj.touching.push(user_data_b.id);
+                            }
+                        }
                     }
                 }
                 Err(TryRecvError::Empty) => break,
@@ -275,33 +602,56 @@ impl VialPhysics {
 
         loop {
             let event = self.contact_force_recv.try_recv();
-
             match event {
-                // Handle the collision event.
-                Ok(_contact_force_event) => {
-                    // eprintln!("Received contact force event: {:?}", contact_force_event);
+                Ok(event) => {
+                    // The solver's impulses include its corrections for
+                    // overlap, so measure the impulse per unit mass as the
+                    // change in velocity that gravity doesn't explain.
+                    let gravity = vector![0.0, -GRAVITY] * self.integration_parameters.dt;
+                    for handle in [event.collider1, event.collider2] {
+                        let Some(collider) = self.collider_set.get(handle) else {
+                            continue;
+                        };
+                        let Some(id) = object_id(collider.user_data) else {
+                            continue;
+                        };
+                        let Some(body) = collider.parent() else {
+                            continue;
+                        };
+                        let (Some(before), Some(after)) = (self.velocities.get(&body), self.rigid_body_set.get(body)) else {
+                            continue;
+                        };
+                        let impulse = (after.linvel() - before - gravity).norm();
+                        if let Some(obj) = objects.get_mut(&id) {
+                            if obj.can_break() && impulse > obj.toughness {
+                                obj.flags.insert(ObjectFlags::BREAK);
+                            }
+                        }
+                    }
                 }
                 Err(TryRecvError::Empty) => break,
                 Err(x) => return Err(x),
             }
-            // Handle the contact force event.
         }
         Ok(())
     }
 
     pub fn project(&mut self, vial: &mut Vial) {
-        let mut map: HashMap<u128, &mut Object> =
-            vial.objects.iter_mut().map(|o| (o.id as u128, o)).collect();
+        let mut map: HashMap<u64, &mut Object> =
+            vial.objects.iter_mut().map(|o| (o.id, o)).collect();
         let mut remove_handles = vec![];
         for (handle, rigid_body) in self.rigid_body_set.iter() {
-            if let Some(obj) = map.remove(&rigid_body.user_data) {
-                let p = rigid_body.translation();
-                obj.pos.x = p.x * M_TO_MM;
-                obj.pos.y = p.y * M_TO_MM;
+            let Some(id) = object_id(rigid_body.user_data) else {
+                continue;
+            };
+            if let Some(obj) = map.remove(&id) {
+                obj.pos = to_mm(rigid_body.translation());
+                obj.velocity = to_mm(rigid_body.linvel());
+                obj.angle = rigid_body.rotation().angle();
                 // dbg!(obj.pos);
             } else {
                 // This vial doesn't have this object anymore. Drop it.
-                remove_handles.push((handle, rigid_body.user_data));
+                remove_handles.push((handle, id));
             }
         }
         for (handle, id) in remove_handles {
@@ -319,63 +669,522 @@ impl VialPhysics {
     }
 }
 
+/// A collider for `shape` at `size`.
+fn shape_collider(shape: &ObjectShape, size: Length) -> ColliderBuilder {
+    let s = size.m();
+    match shape {
+        ObjectShape::Circle => ColliderBuilder::ball(s),
+        ObjectShape::Capsule { half_length } => ColliderBuilder::capsule_x(half_length * s, s),
+        ObjectShape::Box { half } => ColliderBuilder::cuboid(half.x * s, half.y * s),
+        ObjectShape::Polygon(points) => {
+            let points: Vec<Point<Real>> =
+                points.iter().map(|p| point![p.x * s, p.y * s]).collect();
+            ColliderBuilder::convex_hull(&points).unwrap_or_else(|| ColliderBuilder::ball(s))
+        }
+    }
+}
+
+/// The id of the object a body or collider belongs to, if any.
+fn object_id(user_data: u128) -> Option<u64> {
+    let user_data = UserData::from(user_data);
+    user_data.is_object().then_some(user_data.id)
+}
+
+/// Split the glass into polylines (units: mm) for the ground and the left
+/// and right walls. Segments flatter than 45° are ground. The walls carry on
+/// straight up past the top of the vial so objects poured in stay inside.
+fn wall_polylines(vial: &Vial) -> Vec<(u8, Vec<Vec2>)> {
+    let profile = vial.shape.profile(vial.size);
+    let center = vial.size.x / 2.0;
+    let top = profile.last().copied().unwrap_or(vial.size);
+    let above = Vec2::new(top.x, vial.size.y * 1.5);
+    let mut outline: Vec<Vec2> = std::iter::once(above)
+        .chain(profile.iter().rev().copied())
+        .map(|p| Vec2::new(center - p.x / 2.0, p.y))
+        .collect();
+    outline.extend(
+        profile.iter().copied().chain(std::iter::once(above))
+            .map(|p| Vec2::new(center + p.x / 2.0, p.y)),
+    );
+    outline.dedup_by(|a, b| a.distance(*b) < 1e-4);
+
+    let mut polylines: Vec<(u8, Vec<Vec2>)> = vec![];
+    for pair in outline.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let d = b - a;
+        let id = if d.x.abs() > d.y.abs() {
+            GROUND_ID
+        } else if a.x + b.x < 2.0 * center {
+            LEFT_WALL_ID
+        } else {
+            RIGHT_WALL_ID
+        };
+        match polylines.last_mut() {
+            Some((last, points)) if *last == id => points.push(b),
+            _ => polylines.push((id, vec![a, b])),
+        }
+    }
+    polylines
+}
+
+/// Drag per unit depth (units: N/m) on a body showing the flow `width`.
 fn drag_force(
     fluid_density: f32,
     relative_velocity: f32,
-    reference_area: f32,
+    width: Length,
     drag_coefficient: f32,
 ) -> f32 {
-    0.5 * fluid_density * relative_velocity * relative_velocity * reference_area * drag_coefficient
+    0.5 * fluid_density * relative_velocity * relative_velocity * width.m() * drag_coefficient
+}
+
+/// Reynolds number of a body `diameter` wide moving at `speed` (units:
+/// m/s) through a liquid.
+pub(crate) fn reynolds(liquid: &Liquid, speed: f32, diameter: Length) -> f32 {
+    liquid.density * speed * diameter.m() / liquid.viscosity
+}
+
+/// Drag coefficient of a circle, or a cylinder in 3D, at Reynolds number
+/// `re`. This is White's fit, which tends to [CIRCLE_DRAG] at high `re` and
+/// to Stokes-like drag at low `re`.
+pub(crate) fn circle_drag_coefficient(re: f32) -> f32 {
+    let re = re.max(1e-6);
+    CIRCLE_DRAG + 6.8 / re.powf(0.89) + 1.96 / re.sqrt() - 0.0004 * re / (1.0 + 3.64e-7 * re * re)
+}
+
+/// Drag on a circle `diameter` wide moving at `speed` (units: m/s) as
+/// linear damping for a body of `mass` (units: kg per m of depth).
+pub(crate) fn drag_damping(liquid: &Liquid, speed: f32, diameter: Length, mass: f32) -> f32 {
+    // F = c v so c / m is the damping. Where the speed is 0 take the limit.
+    let speed = speed.max(1e-6);
+    let cd = circle_drag_coefficient(reynolds(liquid, speed, diameter));
+    drag_force(liquid.density, speed, diameter, cd) / (speed * mass)
 }
 
-pub(crate) fn circle_wedge_area(r: f32, h: f32) -> f32 {
-    r * r * ((r - h) / r).acos() - (r - h) * (2.0 * r * h - h * h).sqrt()
+/// Speed (units: m/s) at which a circle of `radius` and `density` (units:
+/// kg/m^3) stops accelerating in a liquid. It is negative if the circle
+/// rises.
+pub fn terminal_velocity(liquid: &Liquid, density: f32, radius: Length) -> f32 {
+    let (area, d) = (radius * radius, radius * 2.0);
+    let weight = (density - liquid.density).abs() * GRAVITY * PI * area.m2();
+    let drag = |v: f32| drag_force(liquid.density, v, d, circle_drag_coefficient(reynolds(liquid, v, d)));
+    // Drag grows with speed so bisect.
+    let (mut lo, mut hi) = (0.0, 1.0);
+    while drag(hi) < weight {
+        hi *= 2.0;
+    }
+    for _ in 0..50 {
+        let mid = (lo + hi) / 2.0;
+        if drag(mid) < weight {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let v = (lo + hi) / 2.0;
+    if density < liquid.density {
+        -v
+    } else {
+        v
+    }
 }
 
+/// Convert a point from vial coordinates (units: mm) to the physics world
+/// (units: m).
+pub(crate) fn to_m(p: Vec2) -> Vector<Real> {
+    vector![mm(p.x).m(), mm(p.y).m()]
+}
+
+/// Convert a point from the physics world (units: m) to vial coordinates
+/// (units: mm).
+pub(crate) fn to_mm(p: &Vector<Real>) -> Vec2 {
+    Vec2::new(units::m(p.x).mm(), units::m(p.y).mm())
+}
+
+/// Area of the part of a circle of radius `r` cut off by a chord `h` from
+/// its edge.
+pub(crate) fn circle_wedge_area(r: Length, h: Length) -> Area {
+    let (r, h) = (r.m(), h.m());
+    let area = r * r * ((r - h) / r).acos() - (r - h) * (2.0 * r * h - h * h).sqrt();
+    units::m2(area)
+}
+
+/// Area of a circle of radius `r` at `p` (units: m) below a water surface
+/// at height `w`, or `None` if it is all above.
 pub(crate) fn circle_buoyancy_area(
-    r: f32,
+    r: Length,
     p: &Vector<Real>,
     water_normal: &Vector<Real>,
-    w: f32,
-) -> Option<f32> {
-    let d = p.dot(water_normal) - w; // distance to plane
-    if d > 0.0 {
+    w: Length,
+) -> Option<Area> {
+    let d = p.dot(water_normal) - w.m(); // distance to plane
+    let h = r.m() - d; // depth of the surface above the bottom of the circle
+    if h <= 0.0 {
         None
+    } else if h >= 2.0 * r.m() {
+        Some(units::m2(PI * r.m() * r.m()))
     } else {
-        let h = r - d;
-        let a = PI * r * r;
-        if h > r {
-            Some(a)
-        } else {
-            Some(a - circle_wedge_area(r, h))
-        }
+        Some(circle_wedge_area(r, units::m(h)))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{Layer, Material, ObjectKind, Outcome, Rule, Shape};
+    use crate::units::ml;
+
+    #[test]
+    fn test_wall_polylines() {
+        let vial = Vial::default();
+        let walls = wall_polylines(&vial);
+        let ids: Vec<u8> = walls.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![LEFT_WALL_ID, GROUND_ID, RIGHT_WALL_ID]);
+        assert_eq!(walls[1].1, vec![Vec2::ZERO, Vec2::new(vial.size.x, 0.0)]);
+
+        // The curve of a round bottom is ground until it turns steep.
+        let vial = Vial { shape: Shape::RoundBottom, ..Default::default() };
+        let walls = wall_polylines(&vial);
+        let ids: Vec<u8> = walls.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![LEFT_WALL_ID, GROUND_ID, RIGHT_WALL_ID]);
+        assert!(walls[1].1.len() > 2);
+    }
+
+    #[test]
+    fn test_round_bottom_keeps_objects() {
+        let mut vial = Vial {
+            shape: Shape::RoundBottom,
+            objects: vec![Object {
+                pos: Vec2::new(4.0, 40.0),
+                size: mm(2.0),
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..300 {
+            physics.step();
+        }
+        physics.project(&mut vial);
+        let pos = vial.objects[0].pos;
+        let center = vial.size.x / 2.0;
+        assert!(pos.y > 0.0 && pos.y < 40.0, "{}", pos);
+        assert!((pos.x - center).abs() <= vial.width_at(mm(pos.y)).mm() / 2.0, "{}", pos);
+    }
+
+    #[test]
+    fn test_terminal_velocity() {
+        let r = mm(2.0);
+        assert!((circle_drag_coefficient(1e5) - CIRCLE_DRAG).abs() < 0.01);
+        // Glass beads sink quickly through water and crawl through honey.
+        assert!((terminal_velocity(&Liquid::WATER, GLASS_DENSITY, r) - 0.307).abs() < 1e-3);
+        assert!((terminal_velocity(&Liquid::OIL, GLASS_DENSITY, r) - 0.188).abs() < 1e-3);
+        assert!((terminal_velocity(&Liquid::HONEY, GLASS_DENSITY, r) - 0.00638).abs() < 1e-5);
+        assert!(terminal_velocity(&Liquid::WATER, WATER_DENSITY, r).abs() < 1e-6);
+        // Objects as dense as water rise through honey.
+        let v = terminal_velocity(&Liquid::HONEY, WATER_DENSITY, r);
+        assert!((v + 0.00302).abs() < 1e-5);
+
+        // The simulation settles on the same speed.
+        let mut vial = Vial {
+            layers: vec![Layer::new(0, ml(100.0), Liquid::HONEY)],
+            objects: vec![Object {
+                pos: Vec2::new(12.5, 10.0),
+                size: r,
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..60 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            physics.project(&mut vial);
+        }
+        let (_, body) = physics.rigid_body_set.iter().next().unwrap();
+        let speed = body.linvel().y;
+        assert!((speed + v).abs() < 0.01 * v.abs(), "{} {}", speed, v);
+    }
+
+    /// Run the physics on a single object until it settles and return its
+    /// position (units: mm).
+    fn settle(layers: Vec<Layer>, pos: Vec2) -> Vec2 {
+        let mut vial = Vial {
+            layers,
+            objects: vec![Object {
+                pos,
+                size: mm(2.0),
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..600 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            physics.project(&mut vial);
+        }
+        vial.objects[0].pos
+    }
+
+    #[test]
+    fn test_float_at_surface() {
+        let honey = Layer::new(0, ml(20.0), Liquid::HONEY);
+        let surface = Vial { layers: vec![honey.clone()], ..Default::default() }.surface().mm();
+        let pos = settle(vec![honey], Vec2::new(12.5, 10.0));
+        // Seven tenths of a water-dense object sit below the surface.
+        let below = circle_buoyancy_area(mm(2.0), &to_m(pos), &vector![0.0, 1.0], mm(surface)).unwrap();
+        let fraction = below.m2() / (PI * mm(2.0).m() * mm(2.0).m());
+        assert!((fraction - WATER_DENSITY / Liquid::HONEY.density).abs() < 0.02, "{} {}", pos, fraction);
+    }
+
+    #[test]
+    fn test_waves_bob() {
+        let mut vial = Vial {
+            layers: vec![Layer::new(0, ml(20.0), Liquid::HONEY)],
+            objects: vec![Object {
+                pos: Vec2::new(12.5, 15.0),
+                size: mm(2.0),
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial).with_waves();
+        let run = |physics: &mut VialPhysics, vial: &mut Vial, steps| {
+            let mut ys = vec![];
+            for _ in 0..steps {
+                physics.add_buoyancy_forces(vial);
+                physics.step();
+                physics.project(vial);
+                ys.push(vial.objects[0].pos.y);
+            }
+            ys
+        };
+        run(&mut physics, &mut vial, 600);
+        assert!(physics.waves().unwrap().is_calm(0.01));
+        let rest = vial.objects[0].pos.y;
+        physics.splash(12.5, -ENTER_SPLASH);
+        let ys = run(&mut physics, &mut vial, 120);
+        let low = ys.iter().copied().fold(f32::INFINITY, f32::min);
+        let high = ys.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        assert!(low < rest - 0.05 && high > rest, "{} {} {}", rest, low, high);
+    }
+
+    #[test]
+    fn test_straddle_layers() {
+        let layers = vec![
+            Layer::new(0, ml(30.0), Liquid::HONEY),
+            Layer::new(1, ml(30.0), Liquid::OIL),
+        ];
+        let interface = Vial { layers: layers[..1].to_vec(), ..Default::default() }.surface().mm();
+        let pos = settle(layers, Vec2::new(12.5, interface + 1.0));
+        // Denser than oil and lighter than honey, the object rests on the
+        // honey with a sixth of it below the interface.
+        let below = circle_buoyancy_area(mm(2.0), &to_m(pos), &vector![0.0, 1.0], mm(interface)).unwrap();
+        let fraction = below.m2() / (PI * mm(2.0).m() * mm(2.0).m());
+        let expected = (WATER_DENSITY - Liquid::OIL.density) / (Liquid::HONEY.density - Liquid::OIL.density);
+        assert!((fraction - expected).abs() < 0.02, "{} {} {}", pos, fraction, expected);
+    }
+
+    /// Drop a seed from `height` (units: mm) into `volume` of water in a
+    /// vial of `material` and return whether it broke.
+    fn drop_breaks(material: Material, volume: f32, height: f32) -> bool {
+        let mut vial = Vial {
+            layers: vec![Layer::water(0, ml(volume))],
+            objects: vec![Object {
+                pos: Vec2::new(12.5, height),
+                size: mm(2.0),
+                id: 1,
+                ..Default::default()
+            }],
+            material,
+            ..Default::default()
+        };
+        vial.layers.retain(|l| l.volume() > ml(0.0));
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..120 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            let mut map: HashMap<u64, &mut Object> =
+                vial.objects.iter_mut().map(|o| (o.id, o)).collect();
+            physics.handle_collisions(&mut map).unwrap();
+            physics.project(&mut vial);
+        }
+        vial.objects[0].flags.contains(ObjectFlags::BREAK)
+    }
+
+    #[test]
+    fn test_break_on_impact() {
+        // A seed dropped from the rim into an empty vial breaks but not
+        // one dropped from just above the floor.
+        assert!(drop_breaks(Material::GLASS, 0.0, 75.0));
+        assert!(!drop_breaks(Material::GLASS, 0.0, 5.0));
+        // Shallow liquid slows it down too little from the rim but enough
+        // from lower down.
+        assert!(drop_breaks(Material::GLASS, 20.0, 75.0));
+        assert!(!drop_breaks(Material::GLASS, 20.0, 20.0));
+        // Deep liquid slows it down enough.
+        assert!(!drop_breaks(Material::GLASS, 50.0, 75.0));
+    }
+
+    #[test]
+    fn test_break_depends_on_material() {
+        // Wood gives a little and metal bounces back.
+        assert!(drop_breaks(Material::GLASS, 0.0, 40.0));
+        assert!(!drop_breaks(Material::WOOD, 0.0, 40.0));
+        assert!(!drop_breaks(Material::GLASS, 0.0, 25.0));
+        assert!(drop_breaks(Material::METAL, 0.0, 30.0));
+    }
+
+    #[test]
+    fn test_twig_floats_flat() {
+        // A wooden twig dropped end first turns over to float on its side.
+        let mut vial = Vial {
+            layers: vec![Layer::water(0, ml(60.0))],
+            objects: vec![Object {
+                pos: Vec2::new(12.5, 30.0),
+                size: mm(0.8),
+                shape: ObjectShape::Capsule { half_length: 5.0 },
+                angle: PI / 2.0 - 0.2,
+                material: Material::WOOD,
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let surface = vial.surface().mm();
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..900 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            physics.project(&mut vial);
+        }
+        let twig = &vial.objects[0];
+        assert!(twig.angle.sin().abs() < 0.1, "{}", twig.angle);
+        assert!((twig.pos.y - surface).abs() < twig.size.mm(), "{} {}", twig.pos, surface);
+    }
+
+    #[test]
+    fn test_box_rests_on_floor() {
+        // A tipped box falls onto its long side.
+        let mut vial = Vial {
+            objects: vec![Object {
+                pos: Vec2::new(12.5, 10.0),
+                size: mm(1.0),
+                shape: ObjectShape::Box { half: Vec2::new(3.0, 1.5) },
+                angle: 0.6,
+                id: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..300 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            physics.project(&mut vial);
+        }
+        let block = &vial.objects[0];
+        assert!(block.angle.sin().abs() < 0.05 && block.pos.y < 2.0, "{} {}", block.pos, block.angle);
+    }
+
+    #[test]
+    fn test_rest_on_solid() {
+        // An object falls onto the top of a solid layer, not the glass.
+        let rock = Layer::Solid { id: 0, volume: ml(20.0), material: Material::GLASS, temperature: ROOM_TEMPERATURE };
+        let floor = Vial::default().height_of(ml(20.0)).mm();
+        let on_glass = settle(vec![], Vec2::new(12.5, 30.0));
+        let on_rock = settle(vec![rock], Vec2::new(12.5, 30.0));
+        assert!((on_rock.y - on_glass.y - floor).abs() < 0.1, "{} {} {}", on_rock, on_glass, floor);
+    }
+
+    #[test]
+    fn test_freeze_around_object() {
+        // A seed on the bottom stays there when the water around it freezes
+        // rather than being pushed up through the ice, and a seed dropped
+        // on the ice rests on top of it.
+        let water = Layer::water(0, ml(4.0));
+        let seed = |id, y| Object { pos: Vec2::new(12.5, y), size: mm(2.0), material: Material::METAL, id, ..Default::default() };
+        let mut vial = Vial { layers: vec![water], objects: vec![seed(1, 2.0)], ..Default::default() };
+        let mut physics = VialPhysics::new(&vial);
+        let run = |physics: &mut VialPhysics, vial: &mut Vial| {
+            for _ in 0..300 {
+                physics.add_buoyancy_forces(vial);
+                physics.step();
+                physics.project(vial);
+            }
+        };
+        run(&mut physics, &mut vial);
+        let before = vial.objects[0].pos;
+        assert!(before.y < 2.0, "{}", before);
+        vial.heat(-40.0);
+        assert!(vial.layers[0].is_solid());
+        vial.objects.push(seed(2, 40.0));
+        physics.sync(&vial);
+        run(&mut physics, &mut vial);
+        assert!(vial.objects[0].pos.distance(before) < 0.01, "{} {}", vial.objects[0].pos, before);
+        let ice = vial.height_of(ml(4.0)).mm();
+        assert!(vial.objects[1].pos.y > ice, "{} {}", vial.objects[1].pos, ice);
+        // Once it thaws the seed is free again.
+        vial.heat(40.0);
+        physics.sync(&vial);
+        assert!(physics.held.is_empty());
+    }
+
+    #[test]
+    fn test_rules_in_physics() {
+        // A kernel dropped on a shell merges with it into a seed, and a
+        // plant wrapped around a creature falls with it.
+        let rules = vec![Rule { kinds: [ObjectKind::Shell, ObjectKind::Kernel], outcome: Outcome::Merge(ObjectKind::Seed) }];
+        let object = |kind, pos, id| Object { kind, pos, size: mm(1.5), id, ..Default::default() };
+        let mut vial = Vial {
+            objects: vec![
+                object(ObjectKind::Shell, Vec2::new(5.0, 1.5), 1),
+                object(ObjectKind::Kernel, Vec2::new(5.0, 8.0), 2),
+                Object { stuck_to: Some(4), ..object(ObjectKind::Plant, Vec2::new(15.0, 40.0), 3) },
+                object(ObjectKind::Creature, Vec2::new(20.0, 42.0), 4),
+            ],
+            ..Default::default()
+        };
+        let mut physics = VialPhysics::new(&vial);
+        for _ in 0..120 {
+            physics.add_buoyancy_forces(&vial);
+            physics.step();
+            let mut map: HashMap<u64, &mut Object> = vial.objects.iter_mut().map(|o| (o.id, o)).collect();
+            physics.handle_collisions(&mut map).unwrap();
+            physics.project(&mut vial);
+            if vial.react(&rules) {
+                physics.sync(&vial);
+            }
+        }
+        let kinds: Vec<ObjectKind> = vial.objects.iter().map(|o| o.kind).collect();
+        assert_eq!(kinds, vec![ObjectKind::Plant, ObjectKind::Creature, ObjectKind::Seed]);
+        let (plant, creature) = (&vial.objects[0], &vial.objects[1]);
+        assert!(plant.pos.y < 10.0, "{}", plant.pos);
+        assert!((plant.pos.distance(creature.pos) - Vec2::new(5.0, 2.0).length()).abs() < 0.1);
+    }
 
     #[test]
     fn test_circle_wedge_area() {
-        assert_eq!(circle_wedge_area(1.0, 0.0), 0.0);
-        assert_eq!(circle_wedge_area(1.0, 1.0), PI / 2.0);
+        use units::m;
+        assert_eq!(circle_wedge_area(m(1.0), m(0.0)).m2(), 0.0);
+        assert_eq!(circle_wedge_area(m(1.0), m(1.0)).m2(), PI / 2.0);
+        assert_eq!(circle_wedge_area(mm(1000.0), mm(1000.0)).m2(), PI / 2.0);
         // assert_eq!(circle_wedge_area(1.0, 2.0), PI);
     }
 
     #[test]
     fn test_circle_buoyancy_area() {
-        assert_eq!(
-            circle_buoyancy_area(1.0, &vector![0.0, 0.0], &vector![0.0, 1.0], 0.0),
-            Some(PI / 2.0)
-        );
-        assert_eq!(
-            circle_buoyancy_area(1.0, &vector![0.0, 0.0], &vector![0.0, 1.0], 2.0),
-            Some(PI)
-        );
-        assert_eq!(
-            circle_buoyancy_area(1.0, &vector![0.0, 0.0], &vector![0.0, 1.0], -2.0),
-            None
-        );
+        use units::m;
+        let area = |w| circle_buoyancy_area(m(1.0), &vector![0.0, 0.0], &vector![0.0, 1.0], w).map(|a| a.m2());
+        assert_eq!(area(m(0.0)), Some(PI / 2.0));
+        assert_eq!(area(m(2.0)), Some(PI));
+        assert_eq!(area(m(-2.0)), None);
+        // Partly submerged from either side.
+        assert!((area(m(0.5)).unwrap() + area(m(-0.5)).unwrap() - PI).abs() < 1e-5);
+        assert!(area(m(-0.5)).unwrap() < PI / 4.0);
     }
 }
@@ -0,0 +1,123 @@
+/// Pull of each column back to rest (units: 1/s^2).
+const STIFFNESS: f32 = 40.0;
+/// Pull of each column towards its neighbours (units: 1/s^2).
+const SPREAD: f32 = 2_000.0;
+/// Loss of velocity (units: 1/s).
+const DAMPING: f32 = 1.5;
+/// Steps per call to [Waves::step] to keep the springs stable.
+const SUBSTEPS: usize = 8;
+/// Largest offset from the flat surface as a fraction of the width.
+const MAX_OFFSET: f32 = 0.25;
+
+/// Waves on the surface of a vial's liquid as a 1D height field.
+///
+/// Each column is a spring pulled back to the flat surface and towards its
+/// neighbours. Offsets always average to zero so the liquid's volume is
+/// unchanged.
+#[derive(Debug, Clone)]
+pub struct Waves {
+    width: f32,
+    /// Offset of each column from the flat surface (units: mm).
+    offsets: Vec<f32>,
+    /// Vertical velocity of each column (units: mm/s).
+    velocities: Vec<f32>,
+}
+
+impl Waves {
+    /// Waves across `width` (units: mm) with `columns` columns.
+    pub fn new(width: f32, columns: usize) -> Self {
+        let columns = columns.max(2);
+        Self {
+            width,
+            offsets: vec![0.0; columns],
+            velocities: vec![0.0; columns],
+        }
+    }
+
+    /// Position of the center of column `i` (units: mm).
+    fn column_x(&self, i: usize) -> f32 {
+        self.width * i as f32 / (self.offsets.len() - 1) as f32
+    }
+
+    /// Push the surface at `x` (units: mm) at `speed` (units: mm/s). A
+    /// negative speed pushes it down.
+    pub fn push(&mut self, x: f32, speed: f32) {
+        let f = (x / self.width).clamp(0.0, 1.0) * (self.offsets.len() - 1) as f32;
+        let i = (f as usize).min(self.offsets.len() - 2);
+        let t = f - i as f32;
+        self.velocities[i] += speed * (1.0 - t);
+        self.velocities[i + 1] += speed * t;
+        // Pushing one place moves the rest the other way.
+        let mean = self.velocities.iter().sum::<f32>() / self.velocities.len() as f32;
+        self.velocities.iter_mut().for_each(|v| *v -= mean);
+    }
+
+    /// Offset of the surface at `x` (units: mm) from flat.
+    pub fn offset_at(&self, x: f32) -> f32 {
+        let f = (x / self.width).clamp(0.0, 1.0) * (self.offsets.len() - 1) as f32;
+        let i = (f as usize).min(self.offsets.len() - 2);
+        let t = f - i as f32;
+        self.offsets[i] * (1.0 - t) + self.offsets[i + 1] * t
+    }
+
+    /// Whether the surface is flat to within `tolerance` (units: mm).
+    pub fn is_calm(&self, tolerance: f32) -> bool {
+        self.offsets.iter().all(|o| o.abs() <= tolerance)
+    }
+
+    /// Advance the waves by `dt` (units: s).
+    pub fn step(&mut self, dt: f32) {
+        let n = self.offsets.len();
+        let dt = dt / SUBSTEPS as f32;
+        let max = self.width * MAX_OFFSET;
+        for _ in 0..SUBSTEPS {
+            for i in 0..n {
+                let left = self.offsets[i.saturating_sub(1)];
+                let right = self.offsets[(i + 1).min(n - 1)];
+                let o = self.offsets[i];
+                let acceleration =
+                    -STIFFNESS * o + SPREAD * (left + right - 2.0 * o) - DAMPING * self.velocities[i];
+                self.velocities[i] += acceleration * dt;
+            }
+            for (o, v) in self.offsets.iter_mut().zip(&self.velocities) {
+                *o = (*o + v * dt).clamp(-max, max);
+            }
+            let mean = self.offsets.iter().sum::<f32>() / n as f32;
+            self.offsets.iter_mut().for_each(|o| *o -= mean);
+        }
+    }
+
+    /// The surface as (x, offset) points (units: mm).
+    pub fn points(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.offsets.iter().enumerate().map(|(i, o)| (self.column_x(i), *o))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_waves() {
+        let mut waves = Waves::new(20.0, 16);
+        assert!(waves.is_calm(0.0));
+        waves.push(2.0, -50.0);
+        waves.step(1.0 / 60.0);
+        // The push dips the surface and the rest rises to keep the volume.
+        assert!(waves.offset_at(2.0) < 0.0);
+        let sum: f32 = waves.points().map(|(_, o)| o).sum();
+        assert!(sum.abs() < 1e-4);
+        // The wave travels across and dies down.
+        let mut far = 0.0f32;
+        for _ in 0..60 {
+            waves.step(1.0 / 60.0);
+            far = far.max(waves.offset_at(18.0).abs());
+        }
+        assert!(far > 0.01);
+        assert!(!waves.is_calm(0.001));
+        for _ in 0..600 {
+            waves.step(1.0 / 60.0);
+        }
+        assert!(waves.is_calm(0.01));
+    }
+}